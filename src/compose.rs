@@ -0,0 +1,205 @@
+use std::mem;
+use std::fs;
+use std::path::Path;
+
+use yaml_rust::{Yaml, YamlLoader, YamlEmitter};
+use yaml_rust::yaml::Hash;
+
+use error::{ParampError, Result};
+use validate;
+use {read_file, resolve_file};
+
+/*
+  A yaml file can build on others with `extends`, i.e. a shared module list with a small file per environment:
+
+  * the files named by `extends` are loaded (and extended) first, in order, then the file itself is merged over the top
+  * scalars in a later file replace earlier ones
+  * lists are appended to, unless written as `{replace: [...]}`, in which case they replace what came before
+  * mappings are merged key by key, using the same rules
+
+  Paths in `extends` are relative to the file they're in
+*/
+
+pub fn load(input_file: &str) -> Result<Yaml> {
+
+    let yaml = load_extended(input_file, &mut Vec::new())?;
+
+    //A `{replace: [...]}` in a file that doesn't extend anything has nothing to replace, but is still a list.  Profiles keep theirs until one is applied
+    Ok(match yaml {
+        Yaml::Hash(hash) => Yaml::Hash(hash.into_iter()
+            .map(|(key, value)| match key.as_str() == Some("profiles") {
+                true => (key, value),
+                false => (key, unwrap_replacements(value))
+            })
+            .collect()),
+        yaml => yaml
+    })
+}
+
+fn load_extended(input_file: &str, loading: &mut Vec<String>) -> Result<Yaml> {
+
+    let canonical = fs::canonicalize(input_file)
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or(String::from(input_file));
+
+    if loading.contains(&canonical) {
+        return Err(ParampError::Config(format!("'{}' extends itself (via {})", input_file, loading.join(" -> "))));
+    }
+
+    let mut yaml = read_document(input_file)?;
+
+    let extends = match yaml {
+        Yaml::Hash(ref mut hash) => hash.remove(&Yaml::String(String::from("extends"))),
+        _ => None
+    };
+
+    let bases: Vec<String> = match extends {
+        Some(Yaml::String(base)) => vec![base],
+        Some(Yaml::Array(bases)) => bases.into_iter().filter_map(|base| base.into_string()).collect(),
+        _ => return Ok(yaml)
+    };
+
+    loading.push(canonical);
+
+    let dir = Path::new(input_file).parent().unwrap_or(Path::new(""));
+
+    let mut merged = Yaml::Null;
+
+    for base in bases.iter() {
+        let base_file = dir.join(base).to_string_lossy().into_owned();
+        merged = merge(merged, load_extended(&base_file, loading)?);
+    }
+
+    loading.pop();
+
+    Ok(merge(merged, yaml))
+}
+
+fn read_document(input_file: &str) -> Result<Yaml> {
+
+    let contents = resolve_file(input_file)
+        .and_then(read_file)
+        .map_err(|err| ParampError::io(format!("Could not read '{}'", input_file), err))?;
+
+    let mut documents = YamlLoader::load_from_str(&contents).map_err(|err| ParampError::Config(format!("Could not parse '{}': {}", input_file, err)))?;
+
+    let yaml = match documents.len() {
+        0 => return Err(ParampError::Config(format!("'{}' is empty", input_file))),
+        _ => documents.remove(0)
+    };
+
+    validate::validate(input_file, &contents, &yaml)?;
+
+    Ok(yaml)
+}
+
+pub fn merge(base: Yaml, overlay: Yaml) -> Yaml {
+
+    let overlay = match replacement(overlay) {
+        Ok(list) => return list,
+        Err(overlay) => overlay
+    };
+
+    match (base, overlay) {
+        (Yaml::Hash(mut base), Yaml::Hash(overlay)) => {
+            for (key, value) in overlay.into_iter() {
+
+                if let Some(existing) = base.get_mut(&key) {
+                    let previous = mem::replace(existing, Yaml::Null);
+                    *existing = merge(previous, value);
+                    continue;
+                }
+
+                base.insert(key, merge(Yaml::Null, value));
+            }
+            Yaml::Hash(base)
+        },
+        (Yaml::Array(mut base), Yaml::Array(overlay)) => {
+            base.extend(overlay);
+            Yaml::Array(base)
+        },
+        //An empty value leaves what's already there, use `{replace: []}` to clear a list
+        (base, Yaml::Null) => base,
+        //Merged into an empty mapping, so markers further down are unwrapped too
+        (_, Yaml::Hash(overlay)) => merge(Yaml::Hash(Hash::new()), Yaml::Hash(overlay)),
+        (_, overlay) => overlay
+    }
+}
+
+//Unwraps `{replace: [...]}`, or gives back the value untouched if it isn't one
+
+fn replacement(value: Yaml) -> ::std::result::Result<Yaml, Yaml> {
+
+    let is_replacement = match value {
+        Yaml::Hash(ref hash) => hash.len() == 1 && hash.contains_key(&Yaml::String(String::from("replace"))),
+        _ => false
+    };
+
+    match value {
+        Yaml::Hash(mut hash) if is_replacement => Ok(hash.remove(&Yaml::String(String::from("replace"))).unwrap_or(Yaml::Null)),
+        value => Err(value)
+    }
+}
+
+fn unwrap_replacements(value: Yaml) -> Yaml {
+    match replacement(value) {
+        Ok(list) => unwrap_replacements(list),
+        Err(Yaml::Hash(hash)) => Yaml::Hash(hash.into_iter().map(|(key, value)| (key, unwrap_replacements(value))).collect()),
+        Err(value) => value
+    }
+}
+
+pub fn render(yaml: &Yaml) -> Result<String> {
+
+    let mut output = String::new();
+
+    YamlEmitter::new(&mut output).dump(yaml).map_err(|err| ParampError::Config(format!("Could not render yaml: {:?}", err)))?;
+
+    Ok(output)
+}
+
+/*
+  Applies `profiles.<name>` over the rest of the file.  Lists in the profile replace the file's lists (so a module can be pinned to another version without installing both), everything else is merged with the same rules as `extends`.  Returns the keys the profile set, so they can be reported
+*/
+
+pub fn apply_profile(yaml: Yaml, profile: &str) -> Result<(Yaml, Vec<String>)> {
+
+    let mut hash = match yaml {
+        Yaml::Hash(hash) => hash,
+        _ => return Err(ParampError::Config(format!("Profile '{}' not found: there are no profiles", profile)))
+    };
+
+    let mut profiles = match hash.remove(&Yaml::String(String::from("profiles"))) {
+        Some(Yaml::Hash(profiles)) => profiles,
+        _ => return Err(ParampError::Config(format!("Profile '{}' not found: there are no profiles", profile)))
+    };
+
+    let overlay = match profiles.remove(&Yaml::String(String::from(profile))) {
+        Some(Yaml::Hash(overlay)) => overlay,
+        Some(_) => Hash::new(),
+        None => {
+            let names: Vec<String> = profiles.keys().filter_map(|name| name.as_str().map(String::from)).collect();
+            return Err(ParampError::Config(format!("Profile '{}' not found, expected one of: {}", profile, names.join(", "))));
+        }
+    };
+
+    let keys = overlay.keys().filter_map(|key| key.as_str().map(String::from)).collect();
+
+    for (key, value) in overlay.into_iter() {
+
+        let value = unwrap_replacements(value);
+
+        if let Some(existing) = hash.get_mut(&key) {
+            let previous = mem::replace(existing, Yaml::Null);
+            *existing = match value {
+                Yaml::Array(list) => Yaml::Array(list),
+                value => merge(previous, value)
+            };
+            continue;
+        }
+
+        hash.insert(key, value);
+    }
+
+    Ok((Yaml::Hash(hash), keys))
+}
@@ -0,0 +1,90 @@
+use extract::Plan;
+
+use get_version;
+
+/*
+  Checks the `module.depends.<id>` entries of every module in the build against the modules that will be installed, including those already in the war (see `plan_bundled_module`)
+*/
+
+pub fn check_dependencies(plan: &Plan) -> Vec<String> {
+
+    let mut problems = Vec::new();
+
+    for module in plan.modules.iter() {
+        for &(ref id, ref range) in module.depends.iter() {
+
+            //If a module is installed twice, the last one wins, and amps in the build replace what's in the war
+            match plan.modules.iter().rev().chain(plan.bundled_modules.iter().rev()).find(|installed| installed.id == *id) {
+                None => problems.push(format!("Module '{}' ({}) depends on '{}' ({}), which is not installed", module.id, module.archive, id, range)),
+                Some(installed) => match installed.version {
+                    Some(ref version) => {
+                        if !version_in_range(version, range) {
+                            problems.push(format!("Module '{}' ({}) depends on '{}' ({}), but version '{}' is installed", module.id, module.archive, id, range, version));
+                        }
+                    },
+                    None => problems.push(format!("Module '{}' ({}) depends on '{}' ({}), but '{}' has no module.version", module.id, module.archive, id, range, id))
+                }
+            }
+        }
+    }
+
+    problems
+}
+
+/*
+  Checks `module.repo.version.min` and `module.repo.version.max` against the version of the war being extracted
+*/
+
+pub fn check_repo_versions(plan: &Plan) -> Vec<String> {
+
+    let mut problems = Vec::new();
+
+    let war = match plan.war_version {
+        Some(ref war) => war,
+        None => return problems
+    };
+
+    let war_version = get_version(&war.version).version;
+
+    for module in plan.modules.iter() {
+
+        if let Some(ref min) = module.repo_version_min {
+            if war_version < get_version(min).version {
+                problems.push(format!("Module '{}' ({}) requires at least version '{}', but '{}' is version '{}'", module.id, module.archive, min, war.archive, war.version));
+            }
+        }
+
+        if let Some(ref max) = module.repo_version_max {
+            if war_version > get_version(max).version {
+                problems.push(format!("Module '{}' ({}) requires at most version '{}', but '{}' is version '{}'", module.id, module.archive, max, war.archive, war.version));
+            }
+        }
+    }
+
+    problems
+}
+
+/*
+  Alfresco version ranges are a comma separated list of `from-to` ranges or exact versions, where `*` matches anything.
+
+  i.e, `1.0-*`, `*-2.1`, `1.0-1.5, 2.0` or `*`
+*/
+
+pub fn version_in_range(version: &str, ranges: &str) -> bool {
+
+    let version = get_version(version.trim()).version;
+
+    ranges.split(',')
+        .map(|range| range.trim())
+        .filter(|range| !range.is_empty())
+        .any(|range| {
+
+            let bounds: Vec<&str> = range.split('-').map(|bound| bound.trim()).collect();
+
+            match bounds.len() {
+                1 => bounds[0] == "*" || get_version(bounds[0]).version == version,
+                2 => (bounds[0] == "*" || get_version(bounds[0]).version <= version) && (bounds[1] == "*" || version <= get_version(bounds[1]).version),
+                _ => false
+            }
+        })
+}
@@ -0,0 +1,101 @@
+use std::fs;
+use std::path::Path;
+
+use error::{ParampError, Result};
+
+/*
+  The output directory is never written to directly.  Files are extracted into a sibling staging directory, which is then renamed over the top of the output directory once everything has succeeded.
+
+  As the directories are siblings they live on the same filesystem, so each rename is atomic.
+*/
+
+pub fn staging_dir(output_dir: &str) -> String {
+    sibling_dir(output_dir, "staging")
+}
+
+pub fn previous_dir(output_dir: &str) -> String {
+    sibling_dir(output_dir, "previous")
+}
+
+fn sibling_dir(output_dir: &str, suffix: &str) -> String {
+    format!("{}.{}", output_dir.trim_right_matches('/'), suffix)
+}
+
+pub fn prepare_staging(output_dir: &str) -> Result<String> {
+
+    let staging = staging_dir(output_dir);
+
+    if let Ok(_) = fs::remove_dir_all(&staging) {
+        println!("Clearing stale staging dir: {}", staging);
+    }
+
+    fs::create_dir_all(&staging).map_err(|err| ParampError::io(format!("Could not create staging directory '{}'", staging), err))?;
+
+    Ok(staging)
+}
+
+pub fn replace_output(staging: &str, output_dir: &str, keep_previous: bool) -> Result<()> {
+
+    let output = output_dir.trim_right_matches('/');
+
+    let previous = previous_dir(output_dir);
+
+    let displaced = match keep_previous {
+        true => previous.clone(),
+        false => sibling_dir(output_dir, "old")
+    };
+
+    if Path::new(output).exists() {
+
+        if let Ok(_) = fs::remove_dir_all(&displaced) {
+            println!("Clearing dir: {}", displaced);
+        }
+
+        fs::rename(output, &displaced).map_err(|err| ParampError::io(format!("Could not move existing output directory '{}' aside", output), err))?;
+    }
+
+    fs::rename(staging, output).map_err(|err| ParampError::io(format!("Could not move staging directory '{}' into place", staging), err))?;
+
+    println!("Replaced dir: {}", output);
+
+    if keep_previous {
+        if Path::new(&previous).exists() {
+            println!("Previous version kept at: {}", previous);
+        }
+    } else if let Ok(_) = fs::remove_dir_all(&displaced) {
+        println!("Clearing dir: {}", displaced);
+    }
+
+    Ok(())
+}
+
+/*
+  Swaps the output directory with the `.previous` copy, so running rollback twice gets you back to where you started
+*/
+
+pub fn rollback(output_dir: &str) -> Result<()> {
+
+    let output = output_dir.trim_right_matches('/');
+
+    let previous = previous_dir(output_dir);
+
+    if !Path::new(&previous).exists() {
+        return Err(ParampError::Config(format!("Could not roll back '{}': no previous version at '{}'", output, previous)));
+    }
+
+    if Path::new(output).exists() {
+
+        let swap = sibling_dir(output_dir, "rollback");
+
+        fs::rename(output, &swap).map_err(|err| ParampError::io(format!("Could not move output directory '{}' aside", output), err))?;
+        fs::rename(&previous, output).map_err(|err| ParampError::io(format!("Could not restore previous version '{}'", previous), err))?;
+        fs::rename(&swap, &previous).map_err(|err| ParampError::io(format!("Could not keep rolled back version at '{}'", previous), err))?;
+
+    } else {
+        fs::rename(&previous, output).map_err(|err| ParampError::io(format!("Could not restore previous version '{}'", previous), err))?;
+    }
+
+    println!("Rolled back {} to the previous version", output);
+
+    Ok(())
+}
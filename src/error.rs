@@ -0,0 +1,66 @@
+use std::fmt;
+use std::result;
+
+/*
+  Everything that can go wrong, grouped by what the person running paramp needs to do about it.  Each group has its own exit code so scripts can tell them apart:
+
+  * 1 - validation: the build was checked and refused (conflicts, strict checks, checksums, drift)
+  * 2 - config: the yaml file or command line is wrong
+  * 3 - io: a file or directory couldn't be read or written
+  * 4 - archive: an amp or war is missing, corrupt or unsafe to extract
+  * 5 - network: the packages server couldn't be reached or refused a request
+*/
+
+#[derive(Debug)]
+pub enum ParampError {
+    Validation(String),
+    Config(String),
+    Io(String),
+    Archive(String),
+    Network(String)
+}
+
+pub type Result<T> = result::Result<T, ParampError>;
+
+pub const EXIT_VALIDATION: i32 = 1;
+pub const EXIT_CONFIG: i32 = 2;
+pub const EXIT_IO: i32 = 3;
+pub const EXIT_ARCHIVE: i32 = 4;
+pub const EXIT_NETWORK: i32 = 5;
+
+impl ParampError {
+
+    pub fn io<E: fmt::Display>(message: String, err: E) -> ParampError {
+        ParampError::Io(format!("{}: {}", message, err))
+    }
+
+    pub fn archive<E: fmt::Display>(input_file: &str, err: E) -> ParampError {
+        ParampError::Archive(format!("Could not read archive '{}': {}", input_file, err))
+    }
+
+    pub fn network<E: fmt::Display>(message: String, err: E) -> ParampError {
+        ParampError::Network(format!("{}: {}", message, err))
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            ParampError::Validation(_) => EXIT_VALIDATION,
+            ParampError::Config(_) => EXIT_CONFIG,
+            ParampError::Io(_) => EXIT_IO,
+            ParampError::Archive(_) => EXIT_ARCHIVE,
+            ParampError::Network(_) => EXIT_NETWORK
+        }
+    }
+}
+
+impl fmt::Display for ParampError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParampError::Validation(ref message) |
+            ParampError::Config(ref message) |
+            ParampError::Io(ref message) |
+            ParampError::Archive(ref message) |
+            ParampError::Network(ref message) => write!(f, "{}", message)
+        }
+    }
+}
@@ -0,0 +1,1080 @@
+use zip::read::{ZipArchive, ZipFile};
+use std::fs::{self, File};
+use std::cmp;
+use std::io::{copy, Write, Read, BufReader, BufRead};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use serde_json;
+
+use regex::{self, Regex};
+
+use rayon;
+use filetime::{self, FileTime};
+use time;
+use rayon::prelude::*;
+
+use {create_file_and_dirs, resolve_file, print_progress};
+use properties::Properties;
+use install::sha256_file;
+use error::{ParampError, Result};
+
+pub const MANIFEST_PATH: &'static str = "META-INF/MANIFEST.MF";
+const MODULE_DIR: &'static str = "WEB-INF/classes/alfresco/module/";
+
+lazy_static! {
+
+    static ref DEFAULT_FILEMAP: BTreeMap<String,String> = {
+
+        let mut file_map = BTreeMap::new();
+        file_map.insert(String::from("/config"),String::from("/WEB-INF/classes"));
+        file_map.insert(String::from("/lib"),String::from("/WEB-INF/lib"));
+        file_map.insert(String::from("/licenses"),String::from("/WEB-INF/licenses"));
+        file_map.insert(String::from("/web/jsp"),String::from("/jsp"));
+        file_map.insert(String::from("/web/css"),String::from("/css"));
+        file_map.insert(String::from("/web/images"),String::from("/images"));
+        file_map.insert(String::from("/web/scripts"),String::from("/scripts"));
+        file_map.insert(String::from("/web/php"),String::from("/"));
+
+        file_map
+    };
+
+    static ref EXCLUSION_MAP: HashSet<String> = {
+        let mut exclusion_map: HashSet<String> = HashSet::new();
+
+        exclusion_map.insert(String::from("file-mapping.properties"));
+        exclusion_map.insert(String::from("module.properties"));
+        exclusion_map.insert(String::from(MANIFEST_PATH));
+
+        exclusion_map
+    };
+
+}
+
+/*
+  Where the content of an output file comes from: either an entry within the archive or content we've already read/generated
+*/
+
+#[derive(Debug, Clone)]
+pub enum Source {
+    Entry(String),
+    Content(Vec<u8>)
+}
+
+#[derive(Debug, Clone)]
+pub struct PlannedFile {
+    pub archive: String,
+    pub source: Source,
+    pub modified: i64,
+    pub mode: Option<u32>
+}
+
+/*
+  What to do when two archives write to the same path.  The manifest is excluded as it has its own rules (see `plan_manifest`)
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictMode {
+    Warn,
+    Error,
+    LastWins,
+    FirstWins
+}
+
+#[derive(Debug, Clone)]
+pub struct ConflictPolicy {
+    pub mode: ConflictMode,
+    pub allow: Vec<Regex>
+}
+
+impl Default for ConflictPolicy {
+    fn default() -> ConflictPolicy {
+        ConflictPolicy {
+            mode: ConflictMode::Warn,
+            allow: Vec::new()
+        }
+    }
+}
+
+impl ConflictPolicy {
+    pub fn new(mode: Option<String>, allow: Vec<String>) -> Result<ConflictPolicy> {
+
+        let mode = match mode.as_ref().map(|mode| mode.as_str()) {
+            None | Some("warn") => ConflictMode::Warn,
+            Some("error") => ConflictMode::Error,
+            Some("last_wins") => ConflictMode::LastWins,
+            Some("first_wins") => ConflictMode::FirstWins,
+            Some(other) => return Err(ParampError::Config(format!("Unknown on_conflict policy '{}', expected one of: warn, error, last_wins, first_wins", other)))
+        };
+
+        Ok(ConflictPolicy {
+            mode: mode,
+            allow: allow.iter().map(|pattern| glob_to_regex(pattern)).collect()
+        })
+    }
+
+    pub fn is_allowed(&self, path: &str) -> bool {
+        self.allow.iter().any(|regex| regex.is_match(path))
+    }
+}
+
+/*
+  `include` and `exclude` glob patterns, matched against the output path (after file-mapping).  Excludes win over includes, and if there are no includes everything is included
+*/
+
+#[derive(Debug, Clone, Default)]
+pub struct FileFilter {
+    pub include: Vec<(String, Regex)>,
+    pub exclude: Vec<(String, Regex)>
+}
+
+impl FileFilter {
+    pub fn new(include: Vec<String>, exclude: Vec<String>) -> FileFilter {
+        FileFilter {
+            include: compile_globs(include),
+            exclude: compile_globs(exclude)
+        }
+    }
+
+    /*
+      Returns why a path is excluded, or `None` if it isn't
+    */
+
+    pub fn excluded_by(&self, path: &str) -> Option<String> {
+
+        if let Some(&(ref pattern, _)) = self.exclude.iter().find(|&&(_, ref regex)| regex.is_match(path)) {
+            return Some(format!("matches exclude '{}'", pattern));
+        }
+
+        if !self.include.is_empty() && !self.include.iter().any(|&(_, ref regex)| regex.is_match(path)) {
+            return Some(String::from("not matched by include"));
+        }
+
+        None
+    }
+}
+
+//Keeps the original pattern around for reporting
+fn compile_globs(patterns: Vec<String>) -> Vec<(String, Regex)> {
+    patterns.into_iter().map(|pattern| {
+        let regex = glob_to_regex(&pattern);
+        (pattern, regex)
+    }).collect()
+}
+
+/*
+  An archive to extract, along with any settings that only apply to it:
+
+  * `target` extracts the whole archive under a directory, instead of using its file mappings
+  * `optional` archives are skipped if they don't exist
+  * `sha256` pins the archive's checksum, so a different file is never extracted
+*/
+
+#[derive(Debug, Clone, Default)]
+pub struct InputFile {
+    pub path: String,
+    pub filter: FileFilter,
+    pub target: Option<String>,
+    pub optional: bool,
+    pub sha256: Option<String>
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExcludedFile {
+    pub path: String,
+    pub archive: String,
+    pub reason: String
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Collision {
+    pub archives: Vec<String>,
+    pub winner: String,
+    pub allowed: bool
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedModule {
+    pub archive: String,
+    pub id: String,
+    pub version: Option<String>,
+    pub depends: Vec<(String, String)>,
+    pub repo_version_min: Option<String>,
+    pub repo_version_max: Option<String>,
+    pub path: String
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WarVersion {
+    pub archive: String,
+    pub version: String
+}
+
+/*
+  A plan is the full list of files that will end up in the output directory, keyed by their path relative to the output directory.
+
+  Archives are planned in order, so a later archive replaces the owner of any path an earlier one wrote (last write wins)
+*/
+
+#[derive(Debug, Default)]
+pub struct Plan {
+    pub policy: ConflictPolicy,
+    pub filter: FileFilter,
+    pub archives: Vec<String>,
+    pub dirs: BTreeSet<String>,
+    pub files: BTreeMap<String, PlannedFile>,
+    pub modules: Vec<PlannedModule>,
+    pub bundled_modules: Vec<PlannedModule>,
+    pub collisions: BTreeMap<String, Collision>,
+    pub excluded: Vec<ExcludedFile>,
+    pub war_version: Option<WarVersion>,
+    pub dependency_problems: Vec<String>,
+    pub version_problems: Vec<String>,
+    pub uncached_modules: Vec<String>,
+    pub source_date_epoch: Option<i64>,
+    pub reproducible: bool
+}
+
+impl Plan {
+
+    fn add_file(&mut self, path: String, planned: PlannedFile) {
+
+        if let Some(existing) = self.files.get(&path) {
+            if existing.archive != planned.archive {
+
+                let allowed = self.policy.is_allowed(&path);
+
+                let keep_existing = self.policy.mode == ConflictMode::FirstWins;
+
+                let collision = self.collisions.entry(path.clone()).or_insert_with(|| Collision {
+                    archives: vec![existing.archive.clone()],
+                    winner: existing.archive.clone(),
+                    allowed: allowed
+                });
+
+                collision.archives.push(planned.archive.clone());
+
+                if keep_existing {
+                    return;
+                }
+
+                collision.winner = planned.archive.clone();
+            }
+        }
+
+        self.files.insert(path, planned);
+    }
+
+    /*
+      The time to give a file: the time of its zip entry, clamped to `SOURCE_DATE_EPOCH` if set
+    */
+
+    pub fn modified_time(&self, planned: &PlannedFile) -> i64 {
+        match self.source_date_epoch {
+            Some(epoch) if epoch < planned.modified => epoch,
+            _ => planned.modified
+        }
+    }
+
+    /*
+      The time used for anything that isn't from a zip entry (directories, the install manifest).
+
+      For reproducible builds this can't be the current time, so it's `SOURCE_DATE_EPOCH` or failing that, the newest file in the plan
+    */
+
+    pub fn build_time(&self) -> i64 {
+        match (self.reproducible, self.source_date_epoch) {
+            (false, _) => time::get_time().sec,
+            (true, Some(epoch)) => epoch,
+            (true, None) => self.files.values().map(|planned| planned.modified).max().unwrap_or(0)
+        }
+    }
+
+    pub fn conflict_errors(&self) -> Vec<&String> {
+        match self.policy.mode {
+            ConflictMode::Error => self.collisions.iter()
+                .filter(|&(_, collision)| !collision.allowed)
+                .map(|(path, _)| path)
+                .collect(),
+            _ => Vec::new()
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct PlanReport<'a> {
+    output_dir: &'a str,
+    archives: &'a [String],
+    files: Vec<FileReport<'a>>,
+    modules: &'a [PlannedModule],
+    on_conflict: ConflictMode,
+    collisions: &'a BTreeMap<String, Collision>,
+    excluded: &'a [ExcludedFile],
+    war_version: &'a Option<WarVersion>,
+    dependency_problems: &'a [String],
+    version_problems: &'a [String],
+    uncached_modules: &'a [String]
+}
+
+#[derive(Serialize)]
+struct FileReport<'a> {
+    path: &'a str,
+    archive: &'a str,
+    entry: Option<&'a str>
+}
+
+pub fn plan_output(input_files: &[InputFile], policy: ConflictPolicy, filter: FileFilter, json: bool) -> Result<Plan> {
+
+    let mut plan = Plan {
+        policy: policy,
+        filter: filter,
+        .. Plan::default()
+    };
+
+    for file in input_files.iter() {
+
+        if file.optional && resolve_file(&file.path).is_err() {
+            print_progress(json, &format!("Skipping optional archive '{}': not found", file.path));
+            continue;
+        }
+
+        if let Some(ref expected) = file.sha256 {
+
+            let actual = sha256_file(&file.path).map_err(|err| ParampError::archive(&file.path, err))?;
+
+            if actual != expected.trim().to_lowercase() {
+                return Err(ParampError::Validation(format!("Checksum mismatch for '{}': expected sha256 {}, got {}", file.path, expected, actual)));
+            }
+        }
+
+        plan_archive(file, &mut plan)?;
+    }
+
+    Ok(plan)
+}
+
+fn plan_archive(input: &InputFile, plan: &mut Plan) -> Result<()> {
+
+    let input_file = input.path.as_str();
+
+    let file = resolve_file(input_file).map_err(|err| ParampError::archive(input_file, err))?;
+
+    let mut archive = ZipArchive::new(file).map_err(|err| ParampError::archive(input_file, err))?;
+
+    plan.archives.push(String::from(input_file));
+
+    if input_file.ends_with(".war") {
+        if let Some(version) = detect_war_version(&mut archive) {
+            plan.war_version = Some(WarVersion {
+                archive: String::from(input_file),
+                version: version
+            });
+        }
+    }
+
+    let file_map = match (input.target.as_ref(), archive.by_name("file-mapping.properties")) {
+        (Some(target), _) => {
+            target_map(target, input_file)?
+        },
+        (None, Ok(amp_map)) => {
+            decorate_map(amp_map, input_file)?
+        },
+        _ => {
+            DEFAULT_FILEMAP.clone()
+        }
+    };
+
+    if let Ok(module_file) = archive.by_name("module.properties") {
+        plan_module_file(module_file, input_file, plan)?;
+    }
+
+    if let Ok(mut manifest_file) = archive.by_name(MANIFEST_PATH) {
+        plan_manifest(&mut manifest_file, input_file, plan)?;
+    }
+
+    for i in 0..archive.len() {
+        let file = archive.by_index(i).map_err(|err| ParampError::archive(input_file, err))?;
+
+        if !EXCLUSION_MAP.contains(file.name()) {
+
+            if is_absolute(file.name()) {
+                return Err(ParampError::Archive(format!("Refusing to extract '{}' from '{}': absolute paths are not allowed", file.name(), input_file)));
+            }
+
+            let change_filename = map_path(&format!("/{}", file.name()), &file_map);
+
+            let path = match normalise_path(&change_filename) {
+                Some(path) => path,
+                None => return Err(ParampError::Archive(format!("Refusing to extract '{}' from '{}': '{}' is outside of the output directory", file.name(), input_file, change_filename)))
+            };
+
+            if path.is_empty() {
+                continue;
+            }
+
+            if file.name().ends_with('/') {
+                plan.dirs.insert(path);
+                continue;
+            }
+
+            let excluded = plan.filter.excluded_by(&path).or(input.filter.excluded_by(&path));
+
+            if let Some(reason) = excluded {
+                plan.excluded.push(ExcludedFile {
+                    path: path,
+                    archive: String::from(input_file),
+                    reason: reason
+                });
+            } else {
+
+                let bundled = path.starts_with(MODULE_DIR) && path.ends_with("/module.properties") && path[MODULE_DIR.len()..].matches('/').count() == 1;
+
+                plan.add_file(path.clone(), PlannedFile {
+                    archive: String::from(input_file),
+                    source: Source::Entry(String::from(file.name())),
+                    modified: modified_time(&file),
+                    mode: file.unix_mode()
+                });
+
+                if bundled {
+                    plan_bundled_module(file, input_file, path, plan)?;
+                }
+            }
+        }
+
+    }
+
+    Ok(())
+}
+
+/*
+  Zip entries store a local time without a timezone.  We treat this as UTC, so the same entry always gets the same time
+*/
+
+fn modified_time(file: &ZipFile) -> i64 {
+    file.last_modified().to_timespec().sec
+}
+
+fn set_metadata(output_file: &str, modified: i64, mode: Option<u32>) -> Result<()> {
+
+    //Some tools write a mode of 0, which would leave the file unreadable
+    match mode {
+        Some(mode) if mode & 0o777 != 0 => set_mode(output_file, mode)?,
+        _ => {}
+    }
+
+    set_file_time(output_file, modified)
+}
+
+pub fn set_file_time(output_file: &str, modified: i64) -> Result<()> {
+
+    let time = FileTime::from_seconds_since_1970(cmp::max(modified, 0) as u64, 0);
+
+    filetime::set_file_times(output_file, time, time).map_err(|err| ParampError::io(format!("Could not set the time of '{}'", output_file), err))
+}
+
+/*
+  Writing files changes the time of the directories they're in, so for reproducible builds every directory is set to the build time once everything is written
+*/
+
+pub fn set_dir_times(dir: &str, modified: i64) -> Result<()> {
+
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries {
+            if let Ok(entry) = entry {
+                if entry.file_type().map(|file_type| file_type.is_dir()).unwrap_or(false) {
+                    set_dir_times(&entry.path().to_string_lossy(), modified)?;
+                }
+            }
+        }
+    }
+
+    set_file_time(dir, modified)
+}
+
+//Only the permission bits are kept, amps aren't trusted to set setuid, setgid or sticky bits
+
+#[cfg(unix)]
+fn set_mode(output_file: &str, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(output_file, fs::Permissions::from_mode(mode & 0o777)).map_err(|err| ParampError::io(format!("Could not set the permissions of '{}'", output_file), err))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_output_file: &str, _mode: u32) -> Result<()> {
+    Ok(())
+}
+
+/*
+  Alfresco wars have a `version.properties`, which is what the MMT uses.  Share doesn't, so we fall back to the manifest
+*/
+
+fn detect_war_version(archive: &mut ZipArchive<File>) -> Option<String> {
+
+    if let Ok(version_file) = archive.by_name("WEB-INF/classes/alfresco/version.properties") {
+        if let Ok(properties) = Properties::read(version_file) {
+            if let (Some(major), Some(minor)) = (properties.get("version.major"), properties.get("version.minor")) {
+                return Some(format!("{}.{}.{}", major.trim(), minor.trim(), properties.get("version.revision").unwrap_or("0").trim()));
+            }
+        }
+    }
+
+    if let Ok(manifest_file) = archive.by_name(MANIFEST_PATH) {
+        for line in BufReader::new(manifest_file).lines() {
+            if let Ok(value) = line {
+                if value.starts_with("Implementation-Version:") {
+                    return Some(String::from(value["Implementation-Version:".len()..].trim()));
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/*
+  This function here is just so that Alfresco Share does not complain.
+*/
+
+fn plan_manifest(file: &mut ZipFile, input_file: &str, plan: &mut Plan) -> Result<()> {
+
+    //If a manifest is already planned, we check to see whether `Specification-Version:` is present.  If it is we don't override
+
+    if let Some(existing) = plan.files.get(MANIFEST_PATH) {
+        if let Source::Content(ref content) = existing.source {
+            for line in BufReader::new(&content[..]).lines() {
+                if let Ok(value) = line {
+                    if value.starts_with("Specification-Version:") {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    let mut content = Vec::new();
+
+    file.read_to_end(&mut content).map_err(|err| ParampError::archive(input_file, err))?;
+
+    plan.files.insert(String::from(MANIFEST_PATH), PlannedFile {
+        archive: String::from(input_file),
+        source: Source::Content(content),
+        modified: modified_time(file),
+        mode: file.unix_mode()
+    });
+
+    Ok(())
+}
+
+/*
+  Modules that are already installed in the war (bundled with it, or applied with the MMT) only have their `module.properties` under the module directory, but still count for dependencies
+*/
+
+fn plan_bundled_module(file: ZipFile, input_file: &str, path: String, plan: &mut Plan) -> Result<()> {
+
+    let properties = Properties::read(file).map_err(|err| ParampError::archive(input_file, err))?;
+
+    if let Some(id) = properties.get("module.id").map(|id| String::from(id.trim())) {
+        plan.bundled_modules.push(PlannedModule {
+            archive: String::from(input_file),
+            id: id,
+            version: properties.get("module.version").map(|version| String::from(version.trim())),
+            depends: Vec::new(),
+            repo_version_min: None,
+            repo_version_max: None,
+            path: path
+        });
+    }
+
+    Ok(())
+}
+
+fn plan_module_file(file: ZipFile, input_file: &str, plan: &mut Plan) -> Result<()> {
+
+    let modified = modified_time(&file);
+
+    let mode = file.unix_mode();
+
+    let mut properties = Properties::read(file).map_err(|err| ParampError::archive(input_file, err))?;
+
+    if let Some(id) = properties.get("module.id").map(|id| String::from(id.trim())) {
+
+        let path = match normalise_path(&format!("{}{}/module.properties", MODULE_DIR, id)) {
+            Some(ref path) if path.starts_with(MODULE_DIR) => path.clone(),
+            _ => return Err(ParampError::Archive(format!("Refusing to extract 'module.properties' from '{}': module.id '{}' is outside of the module directory", input_file, id)))
+        };
+
+        properties.set("module.installState", "INSTALLED");
+
+        plan.add_file(path.clone(), PlannedFile {
+            archive: String::from(input_file),
+            source: Source::Content(properties.to_string().into_bytes()),
+            modified: modified,
+            mode: mode
+        });
+
+        let depends = properties.iter()
+            .filter(|&&(ref key, _)| key.starts_with("module.depends."))
+            .map(|&(ref key, ref value)| (String::from(&key["module.depends.".len()..]), value.clone()))
+            .collect();
+
+        plan.modules.push(PlannedModule {
+            archive: String::from(input_file),
+            id: id,
+            version: properties.get("module.version").map(|version| String::from(version.trim())),
+            depends: depends,
+            repo_version_min: properties.get("module.repo.version.min").map(|version| String::from(version.trim())),
+            repo_version_max: properties.get("module.repo.version.max").map(|version| String::from(version.trim())),
+            path: path
+        });
+
+    }
+
+    Ok(())
+}
+
+/*
+  Mirrors the MMT: the default mappings are included unless `include.default=false` is set, and mappings from the amp replace defaults with the same source path.
+
+  Keyed by `mapping_key`, so `web` and `/web/` are the same mapping (the last one in the file wins)
+*/
+
+fn decorate_map(amp_map: ZipFile, input_file: &str) -> Result<BTreeMap<String, String>> {
+
+    let mut return_map = BTreeMap::new();
+
+    let properties = Properties::read(amp_map).map_err(|err| ParampError::archive(input_file, err))?;
+
+    let include_default = properties.get("include.default").map(|value| value.trim() != "false").unwrap_or(true);
+
+    for &(ref from, ref to) in properties.iter().filter(|&&(ref key, _)| key != "include.default") {
+
+        if normalise_path(to).is_none() {
+            return Err(ParampError::Archive(format!("Refusing to use file-mapping.properties from '{}': '{}' maps to '{}', which is outside of the webapp", input_file, from, to)));
+        }
+
+        return_map.insert(mapping_key(from), to.clone());
+    }
+
+    if include_default {
+        for (from, to) in DEFAULT_FILEMAP.iter() {
+            if !return_map.contains_key(&mapping_key(from)) {
+                return_map.insert(mapping_key(from), to.clone());
+            }
+        }
+    }
+
+    Ok(return_map)
+}
+
+//A single mapping that puts everything in the archive under the target directory
+
+fn target_map(target: &str, input_file: &str) -> Result<BTreeMap<String, String>> {
+
+    if normalise_path(target).is_none() {
+        return Err(ParampError::Config(format!("Refusing to extract '{}' to '{}': it is outside of the webapp", input_file, target)));
+    }
+
+    let mut file_map = BTreeMap::new();
+    file_map.insert(mapping_key("/"), String::from(target));
+
+    Ok(file_map)
+}
+
+/*
+  Rewrites a path using the mapping with the longest matching source directory, so `/web/jsp` wins over `/web`.  Only one mapping is ever applied to a path
+*/
+
+fn map_path(path: &str, file_map: &BTreeMap<String, String>) -> String {
+
+    //Sources are already mapping keys, so there's only ever one mapping for each length
+    let matched = file_map.iter()
+        .filter(|&(from, _)| path == from || (path.starts_with(from.as_str()) && path[from.len()..].starts_with('/')))
+        .max_by_key(|&(from, _)| from.len());
+
+    match matched {
+        Some((from, to)) => format!("{}{}", mapping_key(to), &path[from.len()..]),
+        None => String::from(path)
+    }
+}
+
+// Mappings can be written as `web`, `/web` or `/web/`: these are all `/web`.  The root is an empty string so it can be prefixed onto `/file`
+
+fn mapping_key(key: &str) -> String {
+    match key.trim().trim_matches('/') {
+        "" => String::new(),
+        trimmed => format!("/{}", trimmed)
+    }
+}
+
+fn is_absolute(name: &str) -> bool {
+    name.starts_with('/') || name.starts_with('\\') || has_drive(name)
+}
+
+//A drive name is a letter then a colon, i.e, `C:` or `c:x`
+
+fn has_drive(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().map(|c| c.is_ascii_alphabetic()).unwrap_or(false) && chars.next() == Some(':')
+}
+
+/*
+  Resolves `.` and `..` components and strips leading/trailing slashes, so the result can be safely joined onto the output directory.
+
+  Returns `None` if the path would end up outside of the directory it's joined to.  Backslashes are treated as separators, as some tools create zips with them, and a leading drive name (`C:`) is refused as joining it would replace the directory on Windows.
+*/
+
+pub fn normalise_path(path: &str) -> Option<String> {
+
+    let mut components: Vec<&str> = Vec::new();
+
+    for component in path.split(|c| c == '/' || c == '\\') {
+        match component {
+            "" | "." => {},
+            ".." => {
+                if components.pop().is_none() {
+                    return None;
+                }
+            },
+            other => components.push(other)
+        }
+    }
+
+    match components.first() {
+        Some(first) if has_drive(first) => None,
+        _ => Some(components.join("/"))
+    }
+}
+
+pub fn write_plan(plan: &Plan, output_dir: &str) -> Result<()> {
+    write_files(plan, output_dir, |_| true)
+}
+
+/*
+  Writes the planned files for which `selected` returns true.
+
+  As every path has a single owner in the plan, the order files are written in doesn't matter, so entries are extracted in parallel.  Each archive's files are split into chunks, with each chunk opening its own handle on the archive
+*/
+
+pub fn write_files<F>(plan: &Plan, output_dir: &str, selected: F) -> Result<()> where F: Fn(&str) -> bool {
+
+    for dir in plan.dirs.iter() {
+        let output_path = format!("{}/{}", output_dir, dir);
+        fs::create_dir_all(&output_path).map_err(|err| ParampError::io(format!("Could not create directory '{}'", output_path), err))?;
+    }
+
+    let threads = rayon::current_num_threads();
+
+    let mut chunks: Vec<(&str, Vec<(&String, &PlannedFile)>)> = Vec::new();
+
+    for input_file in plan.archives.iter() {
+
+        let files: Vec<(&String, &PlannedFile)> = plan.files.iter()
+            .filter(|&(path, planned)| planned.archive == *input_file && selected(path))
+            .collect();
+
+        if files.is_empty() {
+            continue;
+        }
+
+        println!("Extracting file: {}", input_file);
+
+        let chunk_size = (files.len() + threads - 1) / threads;
+
+        for chunk in files.chunks(chunk_size) {
+            chunks.push((input_file, chunk.to_vec()));
+        }
+    }
+
+    chunks.par_iter().map(|&(input_file, ref files)| {
+
+        let file = resolve_file(input_file).map_err(|err| ParampError::archive(input_file, err))?;
+
+        let mut archive = ZipArchive::new(file).map_err(|err| ParampError::archive(input_file, err))?;
+
+        for &(path, planned) in files.iter() {
+
+            let output_file = format!("{}/{}", output_dir, path);
+
+            let write_error = |err| ParampError::io(format!("Could not write '{}'", output_file), err);
+
+            //Existing files may be read only, so are replaced rather than written over
+            let _ = fs::remove_file(&output_file);
+
+            let mut file_handle = create_file_and_dirs(&output_file).map_err(&write_error)?;
+
+            match planned.source {
+                Source::Entry(ref name) => {
+                    let mut file = archive.by_name(name).map_err(|err| ParampError::archive(input_file, err))?;
+                    copy(&mut file, &mut file_handle).map_err(&write_error)?;
+                },
+                Source::Content(ref content) => {
+                    file_handle.write_all(content).map_err(&write_error)?;
+                }
+            }
+
+            set_metadata(&output_file, plan.modified_time(planned), planned.mode)?;
+        }
+
+        Ok(())
+    }).collect::<Result<Vec<()>>>()?;
+
+    Ok(())
+}
+
+pub fn print_plan(plan: &Plan, output_dir: &str, json: bool) {
+
+    if json {
+
+        let report = PlanReport {
+            output_dir: output_dir,
+            archives: &plan.archives,
+            files: plan.files.iter().map(|(path, planned)| FileReport {
+                path: path,
+                archive: &planned.archive,
+                entry: match planned.source {
+                    Source::Entry(ref name) => Some(name.as_str()),
+                    Source::Content(_) => None
+                }
+            }).collect(),
+            modules: &plan.modules,
+            on_conflict: plan.policy.mode,
+            collisions: &plan.collisions,
+            excluded: &plan.excluded,
+            war_version: &plan.war_version,
+            dependency_problems: &plan.dependency_problems,
+            version_problems: &plan.version_problems,
+            uncached_modules: &plan.uncached_modules
+        };
+
+        println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+
+    } else {
+
+        println!("Dry run: the following would be written to {}\n", output_dir);
+
+        for (path, planned) in plan.files.iter() {
+            println!("  /{} <- {}", path, planned.archive);
+        }
+
+        println!("\nModule properties:\n");
+
+        for module in plan.modules.iter() {
+            println!("  {} -> /{} ({})", module.id, module.path, module.archive);
+        }
+
+        println!("\n{} files from {} archives", plan.files.len(), plan.archives.len());
+
+        print_collisions(plan);
+
+        print_excluded(plan);
+
+        if !plan.uncached_modules.is_empty() {
+
+            println!("\n{} modules aren't in '.ampcache', so their files aren't listed:\n", plan.uncached_modules.len());
+
+            for module in plan.uncached_modules.iter() {
+                println!("  {}", module);
+            }
+        }
+    }
+
+}
+
+pub fn print_collisions(plan: &Plan) {
+
+    if plan.collisions.is_empty() {
+        return;
+    }
+
+    println!("\n{} paths were written by more than one archive:\n", plan.collisions.len());
+
+    for (path, collision) in plan.collisions.iter() {
+
+        let level = match (plan.policy.mode, collision.allowed) {
+            (_, true) => "allowed",
+            (ConflictMode::Error, false) => "error",
+            (ConflictMode::Warn, false) => "warning",
+            _ => "info"
+        };
+
+        println!("  [{}] /{}: {} (kept {})", level, path, collision.archives.join(", "), collision.winner);
+    }
+}
+
+pub fn print_excluded(plan: &Plan) {
+
+    if plan.excluded.is_empty() {
+        return;
+    }
+
+    println!("\n{} files were excluded:\n", plan.excluded.len());
+
+    for excluded in plan.excluded.iter() {
+        println!("  /{} from {}: {}", excluded.path, excluded.archive, excluded.reason);
+    }
+}
+
+// Converts a simple glob (`*`, `**` and `?`) into a regex matching an output path.
+// Leading slashes are ignored so `/WEB-INF/lib/*.jar` and `WEB-INF/lib/*.jar` are the same
+
+pub fn glob_to_regex(pattern: &str) -> Regex {
+
+    let mut regex = String::from("^");
+
+    let mut chars = pattern.trim_left_matches('/').chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => {
+                if chars.peek() == Some(&'*') {
+                    chars.next();
+                    regex.push_str(".*");
+                } else {
+                    regex.push_str("[^/]*");
+                }
+            },
+            '?' => regex.push_str("[^/]"),
+            other => regex.push_str(&regex::escape(&other.to_string()))
+        }
+    }
+
+    regex.push('$');
+
+    Regex::new(&regex).expect("Could not compile glob pattern")
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::process;
+
+    use zip::write::{ZipWriter, FileOptions};
+
+    use error::{ParampError, Result};
+    use super::{normalise_path, is_absolute, target_map, plan_output, InputFile, Plan, ConflictPolicy, FileFilter};
+
+    fn amp(name: &str, entries: &[(&str, &str)]) -> String {
+
+        let path = env::temp_dir().join(format!("paramp-test-{}-{}.amp", process::id(), name));
+
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+
+        for &(entry, contents) in entries.iter() {
+            zip.start_file(entry, FileOptions::default()).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+
+        zip.finish().unwrap();
+
+        path.to_string_lossy().into_owned()
+    }
+
+    fn plan(input_file: &str) -> Result<Plan> {
+
+        let input = InputFile {
+            path: String::from(input_file),
+            .. InputFile::default()
+        };
+
+        let planned = plan_output(&[input], ConflictPolicy::default(), FileFilter::default(), false);
+
+        fs::remove_file(input_file).unwrap();
+
+        planned
+    }
+
+    fn is_archive_error<T>(result: Result<T>) -> bool {
+        match result {
+            Err(ParampError::Archive(_)) => true,
+            _ => false
+        }
+    }
+
+    #[test]
+    fn normalise_plain_paths() {
+        assert_eq!(normalise_path("WEB-INF/lib/a.jar"), Some(String::from("WEB-INF/lib/a.jar")));
+        assert_eq!(normalise_path("/WEB-INF/lib/"), Some(String::from("WEB-INF/lib")));
+        assert_eq!(normalise_path("./a//b/./c"), Some(String::from("a/b/c")));
+        assert_eq!(normalise_path("/"), Some(String::new()));
+    }
+
+    #[test]
+    fn normalise_parent_directories() {
+        assert_eq!(normalise_path("a/b/../c"), Some(String::from("a/c")));
+        assert_eq!(normalise_path("a/.."), Some(String::new()));
+        assert_eq!(normalise_path("../x"), None);
+        assert_eq!(normalise_path("a/../../x"), None);
+        assert_eq!(normalise_path("/.."), None);
+        assert_eq!(normalise_path("a/b/../../../x"), None);
+    }
+
+    #[test]
+    fn normalise_backslashes() {
+        assert_eq!(normalise_path("a\\b\\c"), Some(String::from("a/b/c")));
+        assert_eq!(normalise_path("..\\x"), None);
+        assert_eq!(normalise_path("a\\..\\..\\x"), None);
+    }
+
+    #[test]
+    fn normalise_drive_names() {
+        assert_eq!(normalise_path("C:\\Windows"), None);
+        assert_eq!(normalise_path("/c:/x"), None);
+        assert_eq!(normalise_path("C:x"), None);
+        assert_eq!(normalise_path("a/c:/x"), Some(String::from("a/c:/x")));
+        assert_eq!(normalise_path("1:x"), Some(String::from("1:x")));
+    }
+
+    #[test]
+    fn absolute_names() {
+        assert!(is_absolute("/etc/passwd"));
+        assert!(is_absolute("\\etc\\passwd"));
+        assert!(is_absolute("C:\\Windows\\win.ini"));
+        assert!(is_absolute("c:x"));
+        assert!(!is_absolute("WEB-INF/lib/a.jar"));
+        assert!(!is_absolute("../x"));
+        assert!(!is_absolute("1:x"));
+    }
+
+    #[test]
+    fn target_outside_of_webapp() {
+        assert!(target_map("/WEB-INF/classes", "a.zip").is_ok());
+        assert!(target_map("/..", "a.zip").is_err());
+        assert!(target_map("a/../../x", "a.zip").is_err());
+        assert!(target_map("C:\\x", "a.zip").is_err());
+    }
+
+    #[test]
+    fn entries_outside_of_webapp() {
+        assert!(plan(&amp("safe", &[("config/a.xml", "a")])).is_ok());
+        assert!(is_archive_error(plan(&amp("parent", &[("../evil.jsp", "x")]))));
+        assert!(is_archive_error(plan(&amp("nested-parent", &[("config/../../../evil.jsp", "x")]))));
+        assert!(is_archive_error(plan(&amp("absolute", &[("/etc/evil", "x")]))));
+        assert!(is_archive_error(plan(&amp("backslash", &[("config\\..\\..\\..\\evil.jsp", "x")]))));
+        assert!(is_archive_error(plan(&amp("drive", &[("C:\\evil.jsp", "x")]))));
+    }
+
+    #[test]
+    fn mapping_targets_outside_of_webapp() {
+        assert!(plan(&amp("mapping-safe", &[("file-mapping.properties", "/web=/app"), ("web/a.jsp", "a")])).is_ok());
+        assert!(is_archive_error(plan(&amp("mapping-parent", &[("file-mapping.properties", "/web=/.."), ("web/a.jsp", "a")]))));
+        assert!(is_archive_error(plan(&amp("mapping-nested", &[("file-mapping.properties", "/web=/a/../../x"), ("web/a.jsp", "a")]))));
+        assert!(is_archive_error(plan(&amp("mapping-drive", &[("file-mapping.properties", "/web=C:\\\\x"), ("web/a.jsp", "a")]))));
+    }
+
+    #[test]
+    fn module_id_outside_of_module_directory() {
+
+        let planned = plan(&amp("module-safe", &[("module.properties", "module.id=my-module\nmodule.version=1.0\n")])).unwrap();
+
+        assert_eq!(planned.modules[0].path, "WEB-INF/classes/alfresco/module/my-module/module.properties");
+
+        assert!(is_archive_error(plan(&amp("module-parent", &[("module.properties", "module.id=../../x\n")]))));
+        assert!(is_archive_error(plan(&amp("module-escape", &[("module.properties", "module.id=../../../../../../x\n")]))));
+        assert!(is_archive_error(plan(&amp("module-backslash", &[("module.properties", "module.id=..\\\\..\\\\x\n")]))));
+    }
+}
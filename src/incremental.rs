@@ -0,0 +1,72 @@
+use std::fs;
+use std::path::Path;
+use std::collections::BTreeSet;
+
+use extract::{self, Plan};
+use verify;
+use error::{ParampError, Result};
+
+/*
+  Updates an output directory in place: only files that are missing or whose size/CRC32 differ are written, and only files that are no longer part of the plan are deleted.
+
+  This avoids re-extracting the whole war (and invalidating Tomcat's caches) when only a few files change
+*/
+
+pub fn update_output(plan: &Plan, output_dir: &str) -> Result<()> {
+
+    let drift = verify::compare_output(plan, output_dir)?;
+
+    let changed: BTreeSet<&String> = drift.missing.iter().chain(drift.modified.iter()).collect();
+
+    extract::write_files(plan, output_dir, |path| changed.contains(&String::from(path)))?;
+
+    let output = Path::new(output_dir);
+
+    for path in drift.added.iter() {
+        fs::remove_file(output.join(path)).map_err(|err| ParampError::io(format!("Could not remove stale file '{}'", path), err))?;
+        remove_empty_parents(plan, output, path);
+    }
+
+    let unchanged = plan.files.len() - changed.len();
+
+    println!("\nIncremental update of {}:\n", output_dir);
+
+    for path in drift.missing.iter() {
+        println!("  added:    /{}", path);
+    }
+
+    for path in drift.modified.iter() {
+        println!("  updated:  /{}", path);
+    }
+
+    for path in drift.added.iter() {
+        println!("  removed:  /{}", path);
+    }
+
+    println!("\n{} added, {} updated, {} removed, {} unchanged", drift.missing.len(), drift.modified.len(), drift.added.len(), unchanged);
+
+    Ok(())
+}
+
+//Directories that are left empty by removing stale files are removed as well, unless an archive has them as an entry
+
+fn remove_empty_parents(plan: &Plan, output: &Path, path: &str) {
+
+    let mut dir = Path::new(path).parent();
+
+    while let Some(current) = dir {
+
+        let relative = current.to_string_lossy().replace('\\', "/");
+
+        if relative.is_empty() || plan.dirs.contains(&relative) {
+            break;
+        }
+
+        //This will fail if the directory isn't empty, which is what we want
+        if fs::remove_dir(output.join(current)).is_err() {
+            break;
+        }
+
+        dir = current.parent();
+    }
+}
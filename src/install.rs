@@ -0,0 +1,151 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crypto::sha2::Sha256;
+use crypto::digest::Digest;
+
+use serde_json;
+use time::{self, Timespec};
+
+use extract::{self, Plan};
+use error::{ParampError, Result};
+use {create_file_and_dirs, resolve_file, Download};
+
+/*
+  A record of what went into an output directory, written to `WEB-INF/paramp-install.json` so later runs (and anything else) can tell what's installed without piecing it together from `module.properties` files
+*/
+
+pub const INSTALL_MANIFEST_PATH: &'static str = "WEB-INF/paramp-install.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallManifest {
+    pub paramp_version: String,
+    pub source: String,
+    pub module_type: Option<String>,
+    pub installed_at: String,
+    pub archives: Vec<InstalledArchive>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstalledArchive {
+    pub file: String,
+    pub sha256: String,
+    pub alfresco_module: Option<String>,
+    pub download_url: Option<String>,
+    pub modules: Vec<InstalledModule>,
+    pub files: Vec<String>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstalledModule {
+    pub id: String,
+    pub version: Option<String>
+}
+
+pub fn create_manifest(plan: &Plan, source: &str, module_type: &Option<String>, downloads: &[Download]) -> Result<InstallManifest> {
+
+    let mut archives = Vec::new();
+
+    for archive in plan.archives.iter() {
+
+        let download = downloads.iter().find(|download| download.file == *archive);
+
+        archives.push(InstalledArchive {
+            file: archive.clone(),
+            sha256: sha256_file(archive).map_err(|err| ParampError::archive(archive, err))?,
+            alfresco_module: download.map(|download| download.module.to_string()),
+            download_url: download.map(|download| download.url.clone()),
+            modules: plan.modules.iter()
+                .filter(|module| module.archive == *archive)
+                .map(|module| InstalledModule {
+                    id: module.id.clone(),
+                    version: module.version.clone()
+                })
+                .collect(),
+            files: plan.files.iter()
+                .filter(|&(_, planned)| planned.archive == *archive)
+                .map(|(path, _)| path.clone())
+                .collect()
+        });
+    }
+
+    Ok(InstallManifest {
+        paramp_version: String::from(env!("CARGO_PKG_VERSION")),
+        source: String::from(source),
+        module_type: module_type.clone(),
+        installed_at: time::at_utc(Timespec::new(plan.build_time(), 0)).rfc3339().to_string(),
+        archives: archives
+    })
+}
+
+pub fn write_manifest(manifest: &InstallManifest, plan: &Plan, output_dir: &str) -> Result<()> {
+
+    let output_file = format!("{}/{}", output_dir, INSTALL_MANIFEST_PATH);
+
+    let mut file_handle = create_file_and_dirs(&output_file).map_err(|err| ParampError::io(format!("Could not create install manifest '{}'", output_file), err))?;
+
+    serde_json::to_writer_pretty(&mut file_handle, manifest).map_err(|err| ParampError::io(format!("Could not write install manifest '{}'", output_file), err))?;
+
+    extract::set_file_time(&output_file, plan.build_time())
+}
+
+pub fn read_manifest(output_dir: &str) -> Option<InstallManifest> {
+    match File::open(Path::new(output_dir).join(INSTALL_MANIFEST_PATH)) {
+        Ok(file) => serde_json::from_reader(file).ok(),
+        Err(_) => None
+    }
+}
+
+/*
+  Compares against what was installed last time, so it's obvious which archives actually changed
+*/
+
+pub fn print_changes(previous: &InstallManifest, current: &InstallManifest) {
+
+    println!("\nChanges since last install ({}):\n", previous.installed_at);
+
+    let mut changes = 0;
+
+    for archive in current.archives.iter() {
+        match previous.archives.iter().find(|old| old.file == archive.file) {
+            None => {
+                println!("  added:   {}", archive.file);
+                changes += 1;
+            },
+            Some(old) => if old.sha256 != archive.sha256 {
+                println!("  changed: {}", archive.file);
+                changes += 1;
+            }
+        }
+    }
+
+    for archive in previous.archives.iter() {
+        if !current.archives.iter().any(|new| new.file == archive.file) {
+            println!("  removed: {}", archive.file);
+            changes += 1;
+        }
+    }
+
+    if changes == 0 {
+        println!("  none");
+    }
+}
+
+pub fn sha256_file(input_file: &str) -> io::Result<String> {
+
+    let mut file = resolve_file(input_file)?;
+
+    let mut sh = Sha256::new();
+
+    let mut buffer = [0; 64 * 1024];
+
+    loop {
+        match file.read(&mut buffer)? {
+            0 => break,
+            read => sh.input(&buffer[..read])
+        }
+    }
+
+    Ok(sh.result_str())
+}
@@ -0,0 +1,85 @@
+use std::fs::File;
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use serde_json;
+
+use error::{ParampError, Result};
+use {create_file_and_dirs, Download};
+
+/*
+  A record of the modules that were downloaded, written to `paramp.lock` next to the yaml file.  Each `vendor:name:version:type` has the url it came from and the checksum the server gave for it, so a module that's been republished under the same version can be caught with `--locked`
+*/
+
+pub const LOCK_FILE_NAME: &'static str = "paramp.lock";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LockFile {
+    pub modules: BTreeMap<String, LockedModule>
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockedModule {
+    pub url: String,
+    pub md5: String
+}
+
+pub fn lock_path(input_file: &str) -> String {
+    Path::new(input_file).parent()
+        .unwrap_or(Path::new(""))
+        .join(LOCK_FILE_NAME)
+        .to_string_lossy()
+        .into_owned()
+}
+
+pub fn read_lock(lock_file: &str) -> Result<Option<LockFile>> {
+    match File::open(lock_file) {
+        Ok(file) => serde_json::from_reader(file)
+            .map(Some)
+            .map_err(|err| ParampError::Config(format!("Could not parse lock file '{}': {}", lock_file, err))),
+        Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(ParampError::io(format!("Could not read lock file '{}'", lock_file), err))
+    }
+}
+
+//With `--locked` every module has to be in the lock file already, upgrading a module (or adding a new one) needs a run without it
+
+pub fn check_checksum(lock: &LockFile, module: &str, checksum: &str) -> Result<()> {
+    match lock.modules.get(module) {
+        Some(locked) if locked.md5 != checksum => Err(ParampError::Validation(format!("Checksum of '{}' has changed since it was locked (locked: {}, server: {}), it may have been republished", module, locked.md5, checksum))),
+        Some(_) => Ok(()),
+        None => Err(ParampError::Validation(format!("'{}' isn't in the lock file, run without --locked to add it", module)))
+    }
+}
+
+/*
+  Replaces the modules of this type with what was just downloaded, leaving the other type alone so repo and share builds can share a lock file
+*/
+
+pub fn update_lock(previous: Option<LockFile>, module_type: &str, downloads: &[Download]) -> LockFile {
+
+    let mut lock = previous.unwrap_or_default();
+
+    let suffix = format!(":{}", module_type);
+
+    lock.modules = lock.modules.into_iter()
+        .filter(|&(ref module, _)| !module.ends_with(&suffix))
+        .collect();
+
+    for download in downloads.iter() {
+        lock.modules.insert(download.module.to_string(), LockedModule {
+            url: download.url.clone(),
+            md5: download.checksum.clone()
+        });
+    }
+
+    lock
+}
+
+pub fn write_lock(lock: &LockFile, lock_file: &str) -> Result<()> {
+
+    let mut file_handle = create_file_and_dirs(lock_file).map_err(|err| ParampError::io(format!("Could not create lock file '{}'", lock_file), err))?;
+
+    serde_json::to_writer_pretty(&mut file_handle, lock).map_err(|err| ParampError::io(format!("Could not write lock file '{}'", lock_file), err))
+}
@@ -8,6 +8,10 @@ extern crate memmap;
 extern crate rayon;
 extern crate reqwest;
 extern crate serde;
+extern crate serde_json;
+extern crate crc;
+extern crate time;
+extern crate filetime;
 
 #[macro_use]
 extern crate hyper;
@@ -18,18 +22,33 @@ extern crate lazy_static;
 #[macro_use]
 extern crate serde_derive;
 
-use yaml_rust::{Yaml,YamlLoader};
-use zip::read::{ZipArchive, ZipFile};
+mod error;
+mod extract;
+mod properties;
+mod deploy;
+mod dependencies;
+mod verify;
+mod install;
+mod incremental;
+mod war;
+mod validate;
+mod compose;
+mod lock;
+mod rewrite;
+
+use yaml_rust::Yaml;
 use std::fs::{self, File};
 use std::path::Path;
-use std::io::{self,copy, Write, Read, BufReader, BufRead, Error, ErrorKind};
-use std::collections::{HashMap, HashSet};
+use std::io::{self,copy, Read, Error, ErrorKind};
 
 use crypto::md5::Md5;
 use crypto::digest::Digest;
 use std::fmt;
+use std::process;
+use std::env;
+use std::cmp;
 
-use clap::{Arg, App};
+use clap::{Arg, App, ArgMatches};
 
 use semver::Version;
 
@@ -42,6 +61,8 @@ use reqwest::{Client, StatusCode};
 
 use serde::de::{Deserialize,Deserializer};
 
+use error::{ParampError, Result};
+
 header! { (Token, "TOKEN") => [String] }
 
 lazy_static! {
@@ -62,30 +83,10 @@ lazy_static! {
         Regex::new(r"(?P<major>\d)\.(?P<minor>\d)").unwrap()
     };
 
-    static ref DEFAULT_FILEMAP: HashMap<String,String> = {
-
-        let mut file_map = HashMap::new();
-        file_map.insert(String::from("/config"),String::from("/WEB-INF/classes"));
-        file_map.insert(String::from("/lib"),String::from("/WEB-INF/lib"));
-        file_map.insert(String::from("/licenses"),String::from("/WEB-INF/licenses"));
-        file_map.insert(String::from("/web/jsp"),String::from("/jsp"));
-        file_map.insert(String::from("/web/css"),String::from("/css"));
-        file_map.insert(String::from("/web/images"),String::from("/images"));
-        file_map.insert(String::from("/web/scripts"),String::from("/scripts"));
-        file_map.insert(String::from("/web/php"),String::from("/"));
-
-        file_map
+    static ref INTERPOLATION: Regex = {
+        Regex::new(r"\$\$|\$\{(?P<name>[A-Za-z_][A-Za-z0-9_]*)(:-(?P<default>[^}]*))?\}").unwrap()
     };
 
-    static ref EXCLUSION_MAP: HashSet<String> = {
-        let mut exclusion_map: HashSet<String> = HashSet::new();
-
-        exclusion_map.insert(String::from("file-mapping.properties"));
-        exclusion_map.insert(String::from("module.properties"));
-        exclusion_map.insert(String::from("META-INF/MANIFEST.MF"));
-
-        exclusion_map
-    };
 
 }
 
@@ -98,6 +99,40 @@ struct AmpModule {
 
 }
 
+#[derive(Debug, Clone)]
+struct VersionCheck {
+    module: AmpModule,
+    latest_stable: Option<String>,
+    latest_dev: Option<String>,
+    upgrade: Option<String>,
+    found: bool
+}
+
+#[derive(Debug, Serialize)]
+struct CheckReport {
+    modules: Vec<ModuleReport>,
+    upgrades_pending: usize
+}
+
+#[derive(Debug, Serialize)]
+struct ModuleReport {
+    module: String,
+    current: String,
+    latest_stable: Option<String>,
+    latest_dev: Option<String>,
+    upgrade_available: bool,
+    upgrade: Option<String>,
+    found: bool
+}
+
+#[derive(Debug, Clone)]
+struct Download {
+    file: String,
+    module: AmpModule,
+    url: String,
+    checksum: String
+}
+
 #[derive (Debug, Clone, Deserialize)]
 struct Config {
     url: String,
@@ -113,7 +148,7 @@ struct AmpMatcher {
     regex: Regex
 }
 
-fn string_to_regex<'de,D>(d: D) -> Result<Regex, D::Error>
+fn string_to_regex<'de,D>(d: D) -> std::result::Result<Regex, D::Error>
           where D: Deserializer<'de> {
     Deserialize::deserialize(d).and_then(|regex_str| Regex::new(regex_str).map_err(serde::de::Error::custom))
 }
@@ -126,15 +161,19 @@ struct VersionPair {
 }
 
 impl AmpModule{
-    fn new(module: &str, module_type:&str) -> AmpModule {
+    fn new(module: &str, module_type:&str) -> Result<AmpModule> {
         let parts: Vec<&str> = module.split(":").collect();
 
-        AmpModule{
+        if parts.len() < 3 {
+            return Err(ParampError::Config(format!("Invalid module '{}' in alfresco_modules, expected 'vendor:name:version'", module)));
+        }
+
+        Ok(AmpModule{
             vendor: String::from(parts[0]),
             name: String::from(parts[1]),
             version: String::from(parts[2]),
             module_type: String::from(module_type)
-        }
+        })
 
     }
 }
@@ -178,237 +217,826 @@ fn main() {
         .arg(Arg::with_name("check")
             .help("Check for latest versions")
             .short("c"))
+        .arg(Arg::with_name("write")
+            .help("When Checking: Update alfresco_modules in the yaml file with the upgraded versions")
+            .long("write")
+            .requires("check"))
+        .arg(Arg::with_name("interactive")
+            .help("When Writing: Confirm each upgrade")
+            .long("interactive")
+            .requires("write"))
+        .arg(Arg::with_name("only")
+            .help("When Writing: Only upgrade this module (vendor:name), can be given more than once")
+            .long("only")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .requires("write"))
+        .arg(Arg::with_name("fail_on_upgrades")
+            .help("When Checking: Exit with code 1 if any modules can be upgraded")
+            .long("fail-on-upgrades")
+            .requires("check")
+            .conflicts_with("write"))
         .arg(Arg::with_name("dev")
             .help("When Checking: Include Non-QA Passed modules")
             .short("d"))
-        .get_matches();
+        .arg(Arg::with_name("dry_run")
+            .help("Report the files that would be written without touching the output directory")
+            .long("dry-run"))
+        .arg(Arg::with_name("war")
+            .help("Also create a war file at the given path")
+            .long("war")
+            .takes_value(true))
+        .arg(Arg::with_name("source_date_epoch")
+            .help("Clamp file times to this unix timestamp (defaults to $SOURCE_DATE_EPOCH)")
+            .long("source-date-epoch")
+            .takes_value(true))
+        .arg(Arg::with_name("reproducible")
+            .help("Make the output byte for byte reproducible, and print a digest of it")
+            .long("reproducible"))
+        .arg(Arg::with_name("verify")
+            .help("Compare the output directory with the yaml file and report any differences")
+            .long("verify"))
+        .arg(Arg::with_name("format")
+            .help("Output format for reports")
+            .long("format")
+            .takes_value(true)
+            .possible_values(&["text", "json", "yaml", "table"])
+            .default_value("text"))
+        .arg(Arg::with_name("keep_previous")
+            .help("Keep the replaced output directory as <output_dir>.previous")
+            .long("keep-previous"))
+        .arg(Arg::with_name("incremental")
+            .help("Update the output directory in place, only writing files that have changed")
+            .long("incremental"))
+        .arg(Arg::with_name("rollback")
+            .help("Restore the output directory from <output_dir>.previous")
+            .long("rollback"))
+        .arg(Arg::with_name("strict_deps")
+            .help("Fail if any module dependencies are missing or the wrong version")
+            .long("strict-deps"))
+        .arg(Arg::with_name("validate")
+            .help("Check the yaml file for unknown keys and values of the wrong type, without running anything")
+            .long("validate"))
+        .arg(Arg::with_name("profile")
+            .help("Apply the named profile from the yaml file's profiles")
+            .long("profile")
+            .takes_value(true))
+        .arg(Arg::with_name("render")
+            .help("Print the yaml file after merging in any files it extends, without running anything")
+            .long("render"))
+        .arg(Arg::with_name("locked")
+            .help("Fail if the server's checksum for a module differs from the one in paramp.lock")
+            .long("locked"))
+        .arg(Arg::with_name("strict_versions")
+            .help("Fail if any modules don't support the version of the war")
+            .long("strict-versions"))
+        .get_matches_safe()
+        .unwrap_or_else(|err| {
+            //Usage errors are config errors, but help and version aren't errors at all
+            if err.use_stderr() {
+                eprintln!("{}", err.message);
+                process::exit(error::EXIT_CONFIG);
+            }
+            err.exit()
+        });
+
+    if let Err(err) = run(&matches) {
+        eprintln!("Error: {}", err);
+        process::exit(err.exit_code());
+    }
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
 
     let input_file = matches.value_of("yaml_file").unwrap();
 
-    let yaml = get_yaml(input_file);
+    let format = matches.value_of("format").unwrap_or("text");
+
+    //Only json and yaml are meant to be read by another program, so nothing else can be printed with them
+    let machine_readable = format == "json" || format == "yaml";
+
+    if (format == "yaml" || format == "table") && !matches.is_present("check") {
+        return Err(ParampError::Config(format!("--format {} is only supported when checking versions (-c)", format)));
+    }
+
+    if format != "text" && matches.is_present("write") {
+        return Err(ParampError::Config(format!("--format {} can't be used with --write", format)));
+    }
+
+    let yaml = match matches.value_of("profile") {
+        Some(profile) => {
 
-    let token: Option<String> = matches.value_of("token")
-        .map(|token| String::from(token))
-        .or(get_yaml_string(&yaml, "token"));
+            let base = get_yaml(input_file)?;
+
+            let (yaml, profile_keys) = compose::apply_profile(base.clone(), profile)?;
+
+            //Render prints yaml, which shouldn't be mixed with anything else either
+            if !matches.is_present("render") && !machine_readable {
+                print_sources(matches, &base, profile, &profile_keys);
+            }
+
+            yaml
+        },
+        None => get_yaml(input_file)?
+    };
+
+    if matches.is_present("validate") {
+        println!("'{}' is valid", input_file);
+        return Ok(());
+    }
+
+    if matches.is_present("render") {
+        println!("{}", compose::render(&yaml)?);
+        return Ok(());
+    }
 
-    let module_type: Option<String> = matches.value_of("type")
-        .map(|token| String::from(token))
-        .or(get_yaml_string(&yaml, "type"));
+    let module_type: Option<String> = get_value(matches.value_of("type"), &yaml, "type")?;
 
     if matches.is_present("check") {
 
-        let url: String = matches.value_of("url")
-            .map(|token| String::from(token))
-            .or(get_yaml_string(&yaml, "url"))
+        let url: String = get_value(matches.value_of("url"), &yaml, "url")?
             .unwrap_or(String::from("https://repo.parashift.com.au"));
 
         let mut modules: Vec<AmpModule> = Vec::new();
 
-        modules.append(&mut get_yaml_string_list(&yaml, "alfresco_modules").iter().map(|module| AmpModule::new(&module, "")).collect());
+        for module in get_yaml_string_list(&yaml, "alfresco_modules")?.iter() {
+            modules.push(AmpModule::new(&module, "")?);
+        }
+
         modules.sort();
         modules.dedup();
 
         let include_dev: bool = matches.is_present("dev") || get_yaml_bool(&yaml, "development").unwrap_or(false);
 
         if modules.len() > 0 {
-            if include_dev {
-                println!("Checking versions (Dev included)\n");
-            } else {
-                println!("Checking versions\n");
+            if format == "text" {
+                if include_dev {
+                    println!("Checking versions (Dev included)\n");
+                } else {
+                    println!("Checking versions\n");
+                }
+            }
+
+            let checks = check_versions(&url, modules, include_dev, format != "text")?;
+
+            match format {
+                "json" | "yaml" => print_check_report(&checks, format)?,
+                "table" => print_check_table(&checks),
+                _ => {
+                    print_check_messages(&checks);
+
+                    if matches.is_present("write") {
+                        rewrite::write_upgrades(input_file, &get_upgrades(&checks, matches.values_of("only").map(|only| only.collect()))?, matches.is_present("interactive"))?;
+                    } else {
+                        format_module_list(&checks);
+                    }
+                }
             }
 
+            let pending = checks.iter().filter(|check| check.upgrade.is_some()).count();
 
-            format_module_list(check_versions(&url, modules, include_dev))
+            if pending > 0 && matches.is_present("fail_on_upgrades") {
+                return Err(ParampError::Validation(format!("{} module(s) can be upgraded", pending)));
+            }
+        } else if machine_readable {
+            print_check_report(&[], format)?;
         } else {
             println!("No modules found in yaml file!");
 
         }
 
+    } else if matches.is_present("rollback") {
+
+        deploy::rollback(&get_output_dir(matches.value_of("output_dir"), &yaml)?)?;
+
     } else {
 
-        let output_dir = matches.value_of("output_dir")
-            .map(|dir| String::from(dir))
-            .unwrap_or_else(|| get_yaml_string(&yaml, "output_dir").expect("Could not get target directory from YAML file"));
+        let output_dir: Option<String> = get_value(matches.value_of("output_dir"), &yaml, "output_dir")?;
+
+        let output_war: Option<String> = get_value(matches.value_of("war"), &yaml, "output_war")?;
+
+        if output_dir.is_none() && output_war.is_none() {
+            return Err(ParampError::Config(format!("No output_dir or output_war in '{}', and none given on the command line", input_file)));
+        }
+
+        //Json reports go to stdout, so anything else printed before them goes to stderr
+        let json = matches.value_of("format") == Some("json") && (matches.is_present("dry_run") || matches.is_present("verify"));
 
         let mut files = Vec::new();
 
-        let modules = get_yaml_string_list(&yaml, "alfresco_modules");
+        let mut downloads = Vec::new();
+
+        //Only written once the build has succeeded, and never with --locked
+        let mut lock_update = None;
+
+        let mut uncached_modules = Vec::new();
+
+        let modules = get_yaml_string_list(&yaml, "alfresco_modules")?;
 
         if modules.len() > 0 {
             if let Some(ref mod_type) = module_type {
 
-                let url: String = matches.value_of("url")
-                    .map(|url| String::from(url))
-                    .or(get_yaml_string(&yaml, "url"))
+                let url: String = get_value(matches.value_of("url"), &yaml, "url")?
                     .unwrap_or(String::from("https://repo.parashift.com.au"));
 
+                let token = get_token(matches.value_of("token"), &yaml)?;
 
-                files.append(&mut download_files(&modules, &mod_type, &token.unwrap_or(String::from("")), &url));
+                let lock_file = lock::lock_path(input_file);
+
+                let previous_lock = lock::read_lock(&lock_file)?;
+
+                let locked = matches.is_present("locked") || get_yaml_bool(&yaml, "locked").unwrap_or(false);
+
+                if locked && previous_lock.is_none() {
+                    return Err(ParampError::Config(format!("Lock file '{}' not found, run without --locked to create it", lock_file)));
+                }
+
+                //A dry run only plans what's already in the cache, so nothing is downloaded or written
+                if matches.is_present("dry_run") {
+
+                    let (cached, uncached) = cached_files(&modules, &mod_type)?;
+
+                    files.extend(cached.into_iter().map(|file| extract::InputFile {
+                        path: file,
+                        .. extract::InputFile::default()
+                    }));
+
+                    uncached_modules = uncached;
+
+                } else {
+
+                    downloads = download_files(&modules, &mod_type, &token.unwrap_or(String::from("")), &url, if locked { previous_lock.as_ref() } else { None }, json)?;
+
+                    let lock = lock::update_lock(previous_lock.clone(), &mod_type, &downloads);
+
+                    if !locked && previous_lock.as_ref() != Some(&lock) {
+                        lock_update = Some((lock_file, lock));
+                    }
+
+                    files.extend(downloads.iter().map(|download| extract::InputFile {
+                        path: download.file.clone(),
+                        .. extract::InputFile::default()
+                    }));
+                }
 
             } else {
-                println!("Skipping module download, no module type is set");
+                print_progress(json, "Skipping module download, no module type is set");
             }
         }
 
+        files.append(&mut get_yaml_input_files(&yaml, "files")?);
 
-        if let Ok(_) = fs::remove_dir_all(&output_dir) {
-            println!("Clearing dir: {}", output_dir);
+        if let Some(ref mod_type) = module_type {
+            files.append(&mut get_yaml_input_files(&yaml, &format!("amps_{}", mod_type))?);
         }
 
-        files.append(&mut get_yaml_string_list(&yaml, "files"));
+        let policy = extract::ConflictPolicy::new(get_yaml_string(&yaml, "on_conflict")?, get_yaml_string_list(&yaml, "allow_conflicts")?)?;
 
-        if let Some(ref mod_type) = module_type {
-            files.append(&mut get_yaml_string_list(&yaml, &format!("amps_{}", mod_type)));
+        let filter = extract::FileFilter::new(get_yaml_string_list(&yaml, "include")?, get_yaml_string_list(&yaml, "exclude")?);
+
+        let mut plan = extract::plan_output(&files, policy, filter, json)?;
+
+        plan.uncached_modules = uncached_modules;
+
+        plan.source_date_epoch = match matches.value_of("source_date_epoch").map(|epoch| String::from(epoch)).or(env::var("SOURCE_DATE_EPOCH").ok()) {
+            Some(epoch) => Some(epoch.trim().parse().map_err(|_| ParampError::Config(format!("SOURCE_DATE_EPOCH must be a unix timestamp, got '{}'", epoch)))?),
+            None => None
+        };
+
+        plan.reproducible = matches.is_present("reproducible") || get_yaml_bool(&yaml, "reproducible").unwrap_or(false);
+
+        plan.dependency_problems = dependencies::check_dependencies(&plan);
+        plan.version_problems = dependencies::check_repo_versions(&plan);
+
+        let strict_deps = matches.is_present("strict_deps") || get_yaml_bool(&yaml, "strict_deps").unwrap_or(false);
+        let strict_versions = matches.is_present("strict_versions") || get_yaml_bool(&yaml, "strict_versions").unwrap_or(false);
+
+        //When outputting json the problems are part of the report instead
+        if !json {
+
+            match plan.war_version {
+                Some(ref war) => println!("Detected version '{}' from '{}'", war.version, war.archive),
+                None => if plan.modules.iter().any(|module| module.repo_version_min.is_some() || module.repo_version_max.is_some()) {
+                    println!("Warning: Could not detect the version of the war, skipping module version checks");
+                }
+            }
+
+            print_problems(&plan.dependency_problems, strict_deps);
+            print_problems(&plan.version_problems, strict_versions);
+        }
+
+        if strict_deps && plan.dependency_problems.len() > 0 {
+            return Err(ParampError::Validation(format!("Refusing to continue: {} module dependency problems (strict deps)", plan.dependency_problems.len())));
+        }
+
+        if strict_versions && plan.version_problems.len() > 0 {
+            return Err(ParampError::Validation(format!("Refusing to continue: {} module version problems (strict versions)", plan.version_problems.len())));
+        }
+
+        if matches.is_present("dry_run") {
+            extract::print_plan(&plan, output_dir.as_ref().or(output_war.as_ref()).unwrap(), json);
+        } else if matches.is_present("verify") {
+
+            let output_dir = match output_dir {
+                Some(output_dir) => output_dir,
+                None => return Err(ParampError::Config(format!("No output_dir in '{}' to verify, and none given on the command line", input_file)))
+            };
+
+            let drift = verify::compare_output(&plan, &output_dir)?;
+
+            verify::print_drift(&drift, &output_dir, json);
+
+            if !drift.is_empty() {
+                return Err(ParampError::Validation(format!("{} does not match '{}'", output_dir, input_file)));
+            }
+
+        } else {
+
+            let conflicts = plan.conflict_errors();
+
+            if conflicts.len() > 0 {
+                extract::print_collisions(&plan);
+                return Err(ParampError::Validation(format!("Refusing to continue: {} conflicting paths (on_conflict: error)", conflicts.len())));
+            }
+
+            let keep_previous = matches.is_present("keep_previous") || get_yaml_bool(&yaml, "keep_previous").unwrap_or(false);
+
+            let previous_install = output_dir.as_ref().and_then(|dir| install::read_manifest(dir));
+
+            let manifest = install::create_manifest(&plan, input_file, &module_type, &downloads)?;
+
+            if let Some(ref output_dir) = output_dir {
+
+                if matches.is_present("incremental") || get_yaml_bool(&yaml, "incremental").unwrap_or(false) {
+
+                    incremental::update_output(&plan, output_dir)?;
+
+                    install::write_manifest(&manifest, &plan, output_dir)?;
+
+                    if plan.reproducible {
+                        extract::set_dir_times(output_dir, plan.build_time())?;
+                    }
+
+                } else {
+
+                    let staging = deploy::prepare_staging(output_dir)?;
+
+                    extract::write_plan(&plan, &staging)?;
+
+                    install::write_manifest(&manifest, &plan, &staging)?;
+
+                    if plan.reproducible {
+                        extract::set_dir_times(&staging, plan.build_time())?;
+                    }
+
+                    deploy::replace_output(&staging, output_dir, keep_previous)?;
+                }
+            }
+
+            if let Some(ref output_war) = output_war {
+                war::write_war(&plan, output_war, &manifest)?;
+            }
+
+            if let Some((ref lock_file, ref lock)) = lock_update {
+                lock::write_lock(lock, lock_file)?;
+                println!("Updated lock file '{}'", lock_file);
+            }
+
+            extract::print_collisions(&plan);
+
+            extract::print_excluded(&plan);
+
+            if let Some(ref previous) = previous_install {
+                install::print_changes(previous, &manifest);
+            }
+
+            if plan.reproducible {
+
+                println!("");
+
+                if let Some(ref output_dir) = output_dir {
+                    println!("Output digest: sha256:{}", verify::tree_digest(output_dir)?);
+                }
+
+                if let Some(ref output_war) = output_war {
+                    println!("War digest: sha256:{}", install::sha256_file(output_war).map_err(|err| ParampError::io(format!("Could not read war file '{}'", output_war), err))?);
+                }
+            }
         }
+    }
+
+    Ok(())
+}
+
+/*
+  Shows where each setting came from once a profile is applied: the yaml file, the profile or the command line
+*/
+
+fn print_sources(matches: &ArgMatches, base: &Yaml, profile: &str, profile_keys: &[String]) {
+
+    let cli_args = [
+        ("output_dir", "output_dir"), ("output_war", "war"), ("url", "url"), ("type", "type"), ("token", "token"), ("development", "dev"),
+        ("keep_previous", "keep_previous"), ("incremental", "incremental"), ("strict_deps", "strict_deps"), ("strict_versions", "strict_versions"), ("reproducible", "reproducible")
+    ];
+
+    let mut keys: Vec<String> = match *base {
+        Yaml::Hash(ref hash) => hash.keys()
+            .filter_map(|key| key.as_str().map(String::from))
+            .filter(|key| key != "profiles")
+            .collect(),
+        _ => Vec::new()
+    };
+
+    for key in profile_keys.iter() {
+        if !keys.contains(key) {
+            keys.push(key.clone());
+        }
+    }
+
+    println!("Using profile '{}':\n", profile);
+
+    for key in keys.iter() {
+
+        let from_cli = cli_args.iter().any(|&(name, arg)| name == key && matches.is_present(arg));
+
+        let source = match (from_cli, profile_keys.contains(key)) {
+            (true, _) => String::from("command line"),
+            (false, true) => format!("profile '{}'", profile),
+            (false, false) => String::from("yaml file")
+        };
+
+        println!("  {}: {}", key, source);
+    }
+
+    println!("");
+}
+
+fn print_problems(problems: &[String], strict: bool) {
+    for problem in problems.iter() {
+        match strict {
+            true => println!("Error: {}", problem),
+            false => println!("Warning: {}", problem)
+        }
+    }
+}
 
-        output_files(files, &output_dir);
+fn get_output_dir(cli_value: Option<&str>, yaml: &Yaml) -> Result<String> {
+    get_value(cli_value, yaml, "output_dir")?
+        .ok_or(ParampError::Config(String::from("No output_dir in the yaml file, and none given on the command line")))
+}
+
+/*
+  Command line values override the yaml file.  The yaml value is only read (and interpolated) if there isn't one
+*/
+
+fn get_value(cli_value: Option<&str>, yaml: &Yaml, value: &str) -> Result<Option<String>> {
+    match cli_value {
+        Some(cli_value) => Ok(Some(String::from(cli_value))),
+        None => get_yaml_string(yaml, value)
+    }
+}
+
+/*
+  The token can come from the command line, `token`, an environment variable named by `token_env` or a file named by `token_file`, in that order.  Only the last two keep the token out of the yaml file
+*/
+
+fn get_token(cli_value: Option<&str>, yaml: &Yaml) -> Result<Option<String>> {
+
+    if let Some(token) = get_value(cli_value, yaml, "token")? {
+        return Ok(Some(token));
+    }
+
+    if let Some(name) = get_yaml_string(yaml, "token_env")? {
+        return match env::var(&name) {
+            Ok(token) => Ok(Some(token)),
+            Err(_) => Err(ParampError::Config(format!("token_env is '{}', but that environment variable is not set", name)))
+        };
     }
+
+    if let Some(token_file) = get_yaml_string(yaml, "token_file")? {
+        let token = resolve_file(&token_file)
+            .and_then(read_file)
+            .map_err(|err| ParampError::io(format!("Could not read token_file '{}'", token_file), err))?;
+
+        return Ok(Some(String::from(token.trim())));
+    }
+
+    Ok(None)
 }
 
-fn format_module_list(modules: Vec<AmpModule>) {
+fn format_module_list(checks: &[VersionCheck]) {
 
     println!("\nPaste the following into your yaml file:\n\n```");
     println!("alfresco_modules:");
 
-    for module in modules.iter() {
-        println!("  - {}:{}:{}", module.vendor, module.name, module.version);
+    for check in checks.iter().filter(|check| check.found) {
+        println!("  - {}:{}:{}", check.module.vendor, check.module.name, check.upgrade.as_ref().unwrap_or(&check.module.version));
     }
 
     println!("```");
 
 }
 
-fn check_versions(url: &str, modules: Vec<AmpModule>, include_dev: bool) -> Vec<AmpModule> {
+fn print_check_messages(checks: &[VersionCheck]) {
+    for check in checks.iter() {
+        if let Some(ref version) = check.upgrade {
+            println!("Module '{}' can be upgraded to version '{}'", check.module, version);
+        } else if !check.found {
+            println!("Could not find any versions for '{}'", check.module);
+        }
+    }
+}
 
-    let mut return_modules: Vec<AmpModule> = Vec::new();
+fn print_check_report(checks: &[VersionCheck], format: &str) -> Result<()> {
+
+    let report = CheckReport {
+        modules: checks.iter()
+            .map(|check| ModuleReport {
+                module: format!("{}:{}", check.module.vendor, check.module.name),
+                current: check.module.version.clone(),
+                latest_stable: check.latest_stable.clone(),
+                latest_dev: check.latest_dev.clone(),
+                upgrade_available: check.upgrade.is_some(),
+                upgrade: check.upgrade.clone(),
+                found: check.found
+            })
+            .collect(),
+        upgrades_pending: checks.iter().filter(|check| check.upgrade.is_some()).count()
+    };
 
-    let client = Client::new().expect("Could not create client");
+    match format {
+        "yaml" => {
+            let value = serde_json::to_value(&report).map_err(|err| ParampError::Config(format!("Could not create report: {}", err)))?;
+            println!("{}", compose::render(&json_to_yaml(value))?);
+        },
+        _ => println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default())
+    }
 
-    for module in modules.into_iter() {
-        let submit_url = match include_dev {
-            true => format!("{}/module/{}/{}?dev=true", url, module.vendor, module.name),
-            false => format!("{}/module/{}/{}", url, module.vendor, module.name)
-        };
+    Ok(())
+}
+
+fn json_to_yaml(value: serde_json::Value) -> Yaml {
+    match value {
+        serde_json::Value::Null => Yaml::Null,
+        serde_json::Value::Bool(value) => Yaml::Boolean(value),
+        serde_json::Value::Number(number) => match number.as_i64() {
+            Some(value) => Yaml::Integer(value),
+            None => Yaml::Real(number.to_string())
+        },
+        serde_json::Value::String(value) => Yaml::String(value),
+        serde_json::Value::Array(values) => Yaml::Array(values.into_iter().map(json_to_yaml).collect()),
+        serde_json::Value::Object(map) => Yaml::Hash(map.into_iter().map(|(key, value)| (Yaml::String(key), json_to_yaml(value))).collect())
+    }
+}
+
+fn print_check_table(checks: &[VersionCheck]) {
+
+    let header = [String::from("MODULE"), String::from("CURRENT"), String::from("STABLE"), String::from("DEV"), String::from("UPGRADE")];
+
+    let rows: Vec<[String; 5]> = Some(header).into_iter().chain(checks.iter()
+        .map(|check| [
+            format!("{}:{}", check.module.vendor, check.module.name),
+            check.module.version.clone(),
+            check.latest_stable.clone().unwrap_or(String::from("-")),
+            check.latest_dev.clone().unwrap_or(String::from("-")),
+            match (check.upgrade.as_ref(), check.found) {
+                (Some(version), _) => version.clone(),
+                (None, true) => String::from("-"),
+                (None, false) => String::from("no versions found")
+            }
+        ]))
+        .collect();
+
+    let mut widths = [0; 4];
+
+    for row in rows.iter() {
+        for (width, value) in widths.iter_mut().zip(row.iter()) {
+            *width = cmp::max(*width, value.len());
+        }
+    }
+
+    for row in rows.iter() {
+        println!("{:<5$}  {:<6$}  {:<7$}  {:<8$}  {}", row[0], row[1], row[2], row[3], row[4], widths[0], widths[1], widths[2], widths[3]);
+    }
+}
 
-        let mut response = client.get(&submit_url).expect("Could not create submission")
-            .send()
-            .expect("Could not submit query");
+//The upgrades to write back to the yaml file, limited to the `--only` modules if any were given
 
-        match response.status() {
-            StatusCode::Ok => {
+fn get_upgrades(checks: &[VersionCheck], only: Option<Vec<&str>>) -> Result<Vec<rewrite::Upgrade>> {
 
-                let existing_version = get_version(&module.version);
+    if let Some(ref only) = only {
+        for module in only.iter() {
+            if !checks.iter().any(|check| format!("{}:{}", check.module.vendor, check.module.name) == *module) {
+                return Err(ParampError::Config(format!("'{}' given to --only isn't in alfresco_modules, expected 'vendor:name'", module)));
+            }
+        }
+    }
 
-                let version_array: Vec<String> = response.json().expect("Could not decode json!");
+    Ok(checks.iter()
+        .filter_map(|check| check.upgrade.as_ref().map(|version| rewrite::Upgrade {
+            module: format!("{}:{}", check.module.vendor, check.module.name),
+            from: check.module.version.clone(),
+            to: version.clone()
+        }))
+        .filter(|upgrade| only.as_ref().map(|only| only.contains(&upgrade.module.as_str())).unwrap_or(true))
+        .collect())
+}
 
-                let versions_found = version_array.len() > 0;
+/*
+  Only the list the upgrade is picked from (dev with `-d`, otherwise stable) has to be fetched.  Reports also show the other one, which is left out if it can't be fetched
+*/
 
-                let mut versions: Vec<VersionPair> = version_array.iter()
-                    .map(|version| get_version(&version))
-                    .filter(|pair| pair.version.gt(&existing_version.version))
-                    .collect();
+fn check_versions(url: &str, modules: Vec<AmpModule>, include_dev: bool, report_both: bool) -> Result<Vec<VersionCheck>> {
 
-                if versions.len() > 0 {
+    let mut checks: Vec<VersionCheck> = Vec::new();
 
-                    versions.sort_by(| left, right | left.version.cmp(&right.version).reverse());
+    let client = Client::new().map_err(|err| ParampError::network(String::from("Could not create client"), err))?;
 
-                    let ref candidate = versions[0];
+    for module in modules.into_iter() {
 
-                    println!("Module '{}' can be upgraded to version '{}'", module, candidate.original);
+        let existing_version = get_version(&module.version);
 
-                    return_modules.push(AmpModule {
-                        name: module.name,
-                        module_type: module.module_type,
-                        version: candidate.original.clone(),
-                        vendor: module.vendor
-                    });
+        let stable_url = format!("{}/module/{}/{}", url, module.vendor, module.name);
 
+        let dev_url = format!("{}?dev=true", stable_url);
 
-                } else {
-                    if versions_found {
-                        return_modules.push(module);
-                    } else {
-                        println!("Could not find any versions for '{}'", module);
-                    }
+        let (wanted_url, other_url) = match include_dev {
+            true => (dev_url, stable_url),
+            false => (stable_url, dev_url)
+        };
 
-                }
+        let wanted = get_versions(&client, &wanted_url, &module)?;
 
+        let versions_found = wanted.len() > 0;
 
-            },
-            status => panic!("Could not get '{}' ({})", module, status)
-        }
+        let wanted = latest_version(wanted);
+
+        let other = match report_both {
+            true => get_versions(&client, &other_url, &module).ok().and_then(latest_version),
+            false => None
+        };
+
+        let upgrade = wanted.as_ref().and_then(|candidate| match candidate.version.gt(&existing_version.version) {
+            true => Some(candidate.original.clone()),
+            false => None
+        });
+
+        let (latest_stable, latest_dev) = match include_dev {
+            true => (other, wanted),
+            false => (wanted, other)
+        };
+
+        checks.push(VersionCheck {
+            module: module,
+            latest_stable: latest_stable.map(|pair| pair.original),
+            latest_dev: latest_dev.map(|pair| pair.original),
+            upgrade: upgrade,
+            found: versions_found
+        });
     }
 
-    return return_modules;
+    Ok(checks)
 
 }
 
-fn download_files(modules: &Vec<String>, module_type: &str, token: &str, url: &str) -> Vec<String> {
+fn get_versions(client: &Client, submit_url: &str, module: &AmpModule) -> Result<Vec<VersionPair>> {
 
-    fs::create_dir_all(".ampcache").unwrap();
+    let mut response = client.get(submit_url).map_err(|err| ParampError::network(format!("Could not create request for '{}'", module), err))?
+        .send()
+        .map_err(|err| ParampError::network(format!("Could not check '{}' at {}", module, submit_url), err))?;
 
-    modules.par_iter()
-        .map(|module| AmpModule::new(module, module_type))
+    match response.status() {
+        StatusCode::Ok => {
+            let version_array: Vec<String> = response.json().map_err(|err| ParampError::network(format!("Could not decode versions of '{}'", module), err))?;
+
+            Ok(version_array.iter().map(|version| get_version(&version)).collect())
+        },
+        status => Err(ParampError::Network(format!("Could not get '{}' ({})", module, status)))
+    }
+}
+
+fn latest_version(versions: Vec<VersionPair>) -> Option<VersionPair> {
+    versions.into_iter().max_by(|left, right| left.version.cmp(&right.version))
+}
+
+fn download_files(modules: &Vec<String>, module_type: &str, token: &str, url: &str, locked: Option<&lock::LockFile>, json: bool) -> Result<Vec<Download>> {
+
+    fs::create_dir_all(".ampcache").map_err(|err| ParampError::io(String::from("Could not create '.ampcache'"), err))?;
+
+    let mut amp_modules = Vec::new();
+
+    for module in modules.iter() {
+        amp_modules.push(AmpModule::new(module, module_type)?);
+    }
+
+    let downloads = amp_modules.into_par_iter()
         .map(|module| {
 
-            println!("Checking module:{}", module);
+            print_progress(json, &format!("Checking module:{}", module));
 
-            let client = Client::new().expect("Could not create client");
+            let client = Client::new().map_err(|err| ParampError::network(String::from("Could not create client"), err))?;
 
-            let file_name = format!(".ampcache/{}-{}-{}-{}.amp", module.vendor, module.name, module.version, module.module_type);
+            let file_name = cache_file(&module);
 
             let submit_url = format!("{}/module/{}/{}/{}/{}", url, module.vendor, module.name, module.version, module.module_type);
 
-            let mut response = client.get(&submit_url).expect("Could not create submission")
+            let mut response = client.get(&submit_url).map_err(|err| ParampError::network(format!("Could not create request for '{}'", module), err))?
                 .send()
-                .expect("Could not submit query");
+                .map_err(|err| ParampError::network(format!("Could not check '{}' at {}", module, submit_url), err))?;
 
             match response.status() {
                 StatusCode::Ok => {
                     let mut checksum = String::new();
 
-                    response.read_to_string(&mut checksum).expect("Could not read response");
+                    response.read_to_string(&mut checksum).map_err(|err| ParampError::network(format!("Could not read checksum of '{}'", module), err))?;
 
                     if checksum.len() > 0 {
+
+                        if let Some(lock) = locked {
+                            lock::check_checksum(lock, &module.to_string(), &checksum)?;
+                        }
+
                         let local_file = resolve_file(&file_name);
 
-                        if !local_file.is_ok() || !compare_checksum(local_file.unwrap(), checksum) {
-                            let mut new_file = create_file_and_dirs(&file_name).unwrap();
+                        if !local_file.is_ok() || !compare_checksum(local_file.unwrap(), &checksum) {
+                            let mut new_file = create_file_and_dirs(&file_name).map_err(|err| ParampError::io(format!("Could not create '{}'", file_name), err))?;
 
-                            let mut file_dl = client.get(&*format!("{}.amp", submit_url)).expect("Could not create submission")
+                            let mut file_dl = client.get(&*format!("{}.amp", submit_url)).map_err(|err| ParampError::network(format!("Could not create request for '{}'", module), err))?
                                 .header(Token(String::from(token)))
                                 .send()
-                                .expect("Could not submit request for file");
+                                .map_err(|err| ParampError::network(format!("Could not download '{}'", module), err))?;
 
                             match file_dl.status() {
                                 StatusCode::Ok => {
-                                    println!("Downloading '{}'", module);
-                                    copy(&mut file_dl, &mut new_file).expect("Error saving file!");
+                                    print_progress(json, &format!("Downloading '{}'", module));
+                                    copy(&mut file_dl, &mut new_file).map_err(|err| ParampError::network(format!("Could not save '{}' to '{}'", module, file_name), err))?;
                                 },
-                                status => panic!("Could not get '{}' ({})", module, status)
+                                status => return Err(ParampError::Network(format!("Could not get '{}' ({})", module, status)))
                             }
                         }
 
-                        return Some(file_name);
+                        return Ok(Some(Download {
+                            file: file_name,
+                            url: format!("{}.amp", submit_url),
+                            module: module,
+                            checksum: checksum
+                        }));
                     } else {
-                        panic!("Could not get '{}' (Invalid Server Checksum)", module)
+                        Err(ParampError::Network(format!("Could not get '{}' (Invalid Server Checksum)", module)))
                     }
                 },
                 StatusCode::SeeOther => {
-                    println!("Skipping module '{}' (No '{}' component)", module, module_type);
-                    return None;
+                    print_progress(json, &format!("Skipping module '{}' (No '{}' component)", module, module_type));
+                    return Ok(None);
                 }
-                status => panic!("Could not get '{}' ({})", module, status)
+                status => Err(ParampError::Network(format!("Could not get '{}' ({})", module, status)))
             }
         })
-        .filter(|filename| *filename != None)
-        .map(|filename| filename.unwrap())
-        .collect::<Vec<String>>()
+        .collect::<Result<Vec<Option<Download>>>>()?;
+
+    Ok(downloads.into_iter()
+        .filter(|download| download.is_some())
+        .map(|download| download.unwrap())
+        .collect())
+
+}
+
+fn cache_file(module: &AmpModule) -> String {
+    format!(".ampcache/{}-{}-{}-{}.amp", module.vendor, module.name, module.version, module.module_type)
+}
+
+//The modules already in `.ampcache`, and the ones that aren't
+
+fn cached_files(modules: &Vec<String>, module_type: &str) -> Result<(Vec<String>, Vec<String>)> {
 
+    let mut cached = Vec::new();
 
+    let mut uncached = Vec::new();
+
+    for module in modules.iter() {
+
+        let module = AmpModule::new(module, module_type)?;
+
+        let file_name = cache_file(&module);
+
+        match Path::new(&file_name).is_file() {
+            true => cached.push(file_name),
+            false => uncached.push(module.to_string())
+        }
+    }
+
+    Ok((cached, uncached))
+}
+
+fn print_progress(json: bool, message: &str) {
+    match json {
+        true => eprintln!("{}", message),
+        false => println!("{}", message)
+    }
 }
 
-fn compare_checksum(file: File, checksum: String) -> bool {
+fn compare_checksum(file: File, checksum: &str) -> bool {
 
     let mut sh = Md5::new();
 
@@ -443,7 +1071,7 @@ fn get_version(input: &str) -> VersionPair {
 
                 return VersionPair {
                     original: String::from(input),
-                    version: Version::parse(&doctored_version).unwrap()
+                    version: Version::parse(&doctored_version).unwrap_or(Version::new(0, 0, 0))
                 }
 
             } else if let Some(values) = MAJOR_MINOR_PRE.captures(input) {
@@ -451,14 +1079,14 @@ fn get_version(input: &str) -> VersionPair {
 
                 return VersionPair {
                     original: String::from(input),
-                    version: Version::parse(&doctored_version).unwrap()
+                    version: Version::parse(&doctored_version).unwrap_or(Version::new(0, 0, 0))
                 }
             } else if let Some(values) = MAJOR_MINOR.captures(input) {
                 let doctored_version = format!("{}.{}.0", values.name("major").unwrap().as_str(), values.name("minor").unwrap().as_str());
 
                 return VersionPair {
                     original: String::from(input),
-                    version: Version::parse(&doctored_version).unwrap()
+                    version: Version::parse(&doctored_version).unwrap_or(Version::new(0, 0, 0))
                 }
             } else {
                 return VersionPair {
@@ -474,190 +1102,114 @@ fn get_version(input: &str) -> VersionPair {
 }
 
 
-fn get_yaml_string_list(yaml: &Yaml, value: &str) -> Vec<String> {
+fn get_yaml_string_list(yaml: &Yaml, value: &str) -> Result<Vec<String>> {
     match yaml[value] {
         Yaml::Array(ref array) => {
-            array.into_iter().map(|value| String::from(value.as_str().unwrap())).collect()
+            array.into_iter().map(|item| match item.as_str() {
+                Some(item) => interpolate(item, value),
+                None => Err(ParampError::Config(format!("Every entry in '{}' must be a string, got {:?}", value, item)))
+            }).collect()
         }
-        _ => Vec::new()
+        _ => Ok(Vec::new())
     }
 
 }
 
-fn get_yaml_bool(yaml:&Yaml, value:&str) -> Option<bool> {
+/*
+  Entries in a file list are either just a path, or a mapping with a `path` and settings for just that archive (see `extract::InputFile`)
+*/
+
+fn get_yaml_input_files(yaml: &Yaml, value: &str) -> Result<Vec<extract::InputFile>> {
     match yaml[value] {
-        Yaml::Boolean(ref yaml_value) => Some(yaml_value.clone()),
-        _ => None
+        Yaml::Array(ref array) => {
+            array.into_iter().map(|entry| match *entry {
+                Yaml::String(ref path) => Ok(extract::InputFile {
+                    path: interpolate(path, value)?,
+                    .. extract::InputFile::default()
+                }),
+                Yaml::Hash(_) => Ok(extract::InputFile {
+                    path: get_yaml_string(entry, "path")?.ok_or(ParampError::Config(format!("Every mapping in '{}' must have a path, got {:?}", value, entry)))?,
+                    filter: extract::FileFilter::new(get_yaml_string_list(entry, "include")?, get_yaml_string_list(entry, "exclude")?),
+                    target: get_yaml_string(entry, "target")?,
+                    optional: get_yaml_bool(entry, "optional").unwrap_or(false),
+                    sha256: get_yaml_string(entry, "sha256")?
+                }),
+                _ => Err(ParampError::Config(format!("Every entry in '{}' must be a path or a mapping with a path, got {:?}", value, entry)))
+            }).collect()
+        }
+        _ => Ok(Vec::new())
     }
 }
 
-fn get_yaml_string(yaml: &Yaml, value: &str) -> Option<String> {
+fn get_yaml_bool(yaml:&Yaml, value:&str) -> Option<bool> {
     match yaml[value] {
-        Yaml::String(ref yaml_value) => Some(yaml_value.clone()),
+        Yaml::Boolean(ref yaml_value) => Some(yaml_value.clone()),
         _ => None
     }
 }
 
-fn get_yaml(input_file: &str) -> Yaml {
-    match read_file(resolve_file(input_file).unwrap()) {
-        Ok(contents) => YamlLoader::load_from_str(&contents).unwrap()[0].clone(),
-        Err(_) => panic!("Could not read file")
-    }
-}
-
-fn output_files(input_files: Vec<String>, output_dir: &str) {
-
-    for file in input_files.iter() {
-        generate_output(&file, output_dir);
+fn get_yaml_string(yaml: &Yaml, value: &str) -> Result<Option<String>> {
+    match yaml[value] {
+        Yaml::String(ref yaml_value) => interpolate(yaml_value, value).map(Some),
+        _ => Ok(None)
     }
-
 }
 
-fn generate_output(input_file: &str, output_dir: &str) {
-
-    println!("Extracting file: {}", input_file);
-
-    let file = resolve_file(input_file).unwrap();
-
-    let mut archive = ZipArchive::new(file).unwrap();
+/*
+  Replaces `${NAME}` with the environment variable `NAME`, and `${NAME:-default}` with `default` if it's unset or empty.  `$$` is a literal `$`.
 
-    let file_map = match archive.by_name("file-mapping.properties") {
-        Ok(amp_map) => {
-            decorate_map(amp_map)
-        },
-        _ => {
-            DEFAULT_FILEMAP.clone()
-        }
-    };
+  Using an unset variable without a default is an error, rather than quietly becoming an empty string
+*/
 
-    if let Ok(module_file) = archive.by_name("module.properties") {
-        create_module_file(module_file, output_dir);
-    }
+fn interpolate(input: &str, value: &str) -> Result<String> {
 
-    if let Ok(mut manifest_file) = archive.by_name("META-INF/MANIFEST.MF") {
-        handle_manifests(&mut manifest_file, output_dir);
-    }
+    let mut output = String::new();
 
+    let mut last = 0;
 
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i).unwrap();
+    for captures in INTERPOLATION.captures_iter(input) {
 
-        if !EXCLUSION_MAP.contains(file.name()) {
+        let whole = captures.get(0).unwrap();
 
-            let mut change_filename = String::from(format!("/{}",file.name()));
+        output.push_str(&input[last..whole.start()]);
 
-            for (from, to) in &file_map {
-                if change_filename.starts_with(from) {
-                    change_filename = format!("{}{}", to , &change_filename[from.len()..]);
-                }
-            }
+        last = whole.end();
 
-            if let Ok(mut file_handle) = create_file_and_dirs(&*format!("{}/{}", output_dir, change_filename)) {
-                copy(&mut file, &mut file_handle).unwrap();
+        let name = match captures.name("name") {
+            Some(name) => name.as_str(),
+            None => {
+                output.push('$');
+                continue;
             }
+        };
 
-
-        }
-
-    }
-}
-
-/*
-  This function here is just so that Alfresco Share does not complain.
-*/
-
-fn handle_manifests(file: &mut ZipFile, output_dir: &str) {
-
-    let output_file = format!("{}/{}", output_dir, file.name());
-
-    //If the file exists, we check to see whether `Specification-Version:` is present.  If it is we don't override
-
-    if let Ok(existing_file) = File::open(&output_file) {
-        let reader = BufReader::new(existing_file);
-
-        for line in reader.lines() {
-            if let Ok(value)  = line {
-                if value.starts_with("Specification-Version:") {
-                    return;
-                }
-            }
+        match (env::var(name), captures.name("default")) {
+            (Ok(ref variable), Some(ref default)) if variable.is_empty() => output.push_str(default.as_str()),
+            (Ok(variable), _) => output.push_str(&variable),
+            (Err(_), Some(default)) => output.push_str(default.as_str()),
+            (Err(_), None) => return Err(ParampError::Config(format!("Environment variable '{}' used in '{}' is not set", name, value)))
         }
     }
 
-    if let Ok(mut file_handle) = create_file_and_dirs(&output_file) {
-        copy(file, &mut file_handle).expect("Could not write manifest file");
-    }
+    output.push_str(&input[last..]);
 
+    Ok(output)
 }
 
-fn create_module_file(file: ZipFile, output_dir: &str) {
-
-    let reader = BufReader::new(file);
-
-    let mut output_file: Option<String> = None;
-
-    let mut output_content = String::new();
-
-    for line in reader.lines() {
-
-        if let Ok(property) = line {
-            let components: Vec<&str> = property.split("=").collect();
-            if components.len() == 2 {
-
-                let name = components[0].trim();
-                let value = components[1].trim();
-
-                output_content.push_str(&*format!("{}={}\n", name, value));
-
-                if name == "module.id" {
-                    output_file = Some(format!("{}/WEB-INF/classes/alfresco/module/{}/module.properties", output_dir, value));
-                }
-            }
-        }
-
-    }
-
-    if let Some(file_name) = output_file {
-
-        if let Ok(mut file_handle) = create_file_and_dirs(&*file_name) {
-            output_content.push_str("module.installState=INSTALLED\n");
-
-            file_handle.write(&output_content.into_bytes()).unwrap();
-        }
-
-    }
-
+fn get_yaml(input_file: &str) -> Result<Yaml> {
+    compose::load(input_file)
 }
 
 fn create_file_and_dirs(file: &str) -> io::Result<File> {
-    create_parent_dirs(file);
+    create_parent_dirs(file)?;
     return File::create(file);
 }
 
-fn create_parent_dirs(file: &str) {
-    let path = Path::new(file);
-    fs::create_dir_all(path.parent().unwrap()).unwrap();
-}
-
-fn decorate_map(amp_map: ZipFile) -> HashMap<String, String> {
-
-    let mut return_map = DEFAULT_FILEMAP.clone();
-
-    let reader = BufReader::new(amp_map);
-
-    for line in reader.lines() {
-        match line {
-            Ok(map) => {
-                let components: Vec<&str> = map.split("=").collect();
-                if components.len() == 2 {
-                    return_map.insert(String::from(components[0]), String::from(components[1]));
-                }
-            },
-            _=> {}
-        }
+fn create_parent_dirs(file: &str) -> io::Result<()> {
+    match Path::new(file).parent() {
+        Some(parent) => fs::create_dir_all(parent),
+        None => Ok(())
     }
-
-    return_map
 }
 
 fn read_file(mut file: File) -> io::Result<String> {
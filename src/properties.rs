@@ -0,0 +1,439 @@
+use std::fmt;
+use std::io::{self, Read};
+
+/*
+  A reader and writer for Java `.properties` files, following the rules of `java.util.Properties`:
+
+  * `=`, `:` or whitespace separate keys from values
+  * `#` and `!` start comment lines
+  * a line ending with an odd number of `\` continues onto the next line
+  * `\t`, `\n`, `\r`, `\f` and `\uXXXX` escapes, with any other escaped character being itself
+
+  Keys keep the order they were first seen in, so a file can be read and written back out without shuffling it
+*/
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Properties {
+    entries: Vec<(String, String)>
+}
+
+impl Properties {
+
+    pub fn new() -> Properties {
+        Properties::default()
+    }
+
+    /*
+      Properties files are ISO-8859-1 according to the spec, but plenty are written as UTF-8 these days.  We try UTF-8 first and fall back to ISO-8859-1
+    */
+
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Properties> {
+
+        let mut bytes = Vec::new();
+
+        reader.read_to_end(&mut bytes)?;
+
+        let contents = match String::from_utf8(bytes) {
+            Ok(contents) => contents,
+            Err(err) => err.into_bytes().iter().map(|&byte| byte as char).collect()
+        };
+
+        Ok(Properties::parse(&contents))
+    }
+
+    pub fn parse(input: &str) -> Properties {
+
+        let mut properties = Properties::new();
+
+        let normalised = input.replace("\r\n", "\n").replace('\r', "\n");
+
+        let mut lines = normalised.lines();
+
+        while let Some(line) = lines.next() {
+
+            let mut logical = String::from(line.trim_left_matches(is_whitespace));
+
+            if logical.is_empty() || logical.starts_with('#') || logical.starts_with('!') {
+                continue;
+            }
+
+            while ends_with_continuation(&logical) {
+                logical.pop();
+
+                match lines.next() {
+                    Some(next) => logical.push_str(next.trim_left_matches(is_whitespace)),
+                    None => break
+                }
+            }
+
+            let (key, value) = split_line(&logical);
+
+            properties.set(&unescape(key), &unescape(value));
+        }
+
+        properties
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.iter()
+            .find(|&&(ref name, _)| name == key)
+            .map(|&(_, ref value)| value.as_str())
+    }
+
+    /*
+      Later values replace earlier ones, as they do in Java, but the key stays where it was first seen
+    */
+
+    pub fn set(&mut self, key: &str, value: &str) {
+        match self.entries.iter().position(|&(ref name, _)| name == key) {
+            Some(index) => self.entries[index].1 = String::from(value),
+            None => self.entries.push((String::from(key), String::from(value)))
+        }
+    }
+
+    pub fn iter(&self) -> ::std::slice::Iter<(String, String)> {
+        self.entries.iter()
+    }
+
+}
+
+impl fmt::Display for Properties {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+
+        for &(ref key, ref value) in self.entries.iter() {
+            writeln!(f, "{}={}", escape(key, true), escape(value, false))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn is_whitespace(c: char) -> bool {
+    c == ' ' || c == '\t' || c == '\u{c}'
+}
+
+fn ends_with_continuation(line: &str) -> bool {
+    line.chars().rev().take_while(|&c| c == '\\').count() % 2 == 1
+}
+
+/*
+  Finds the end of the key (the first unescaped separator) and the start of the value, which may have whitespace either side of a single `=` or `:`
+*/
+
+fn split_line(line: &str) -> (&str, &str) {
+
+    let mut escaped = false;
+
+    let mut key_end = line.len();
+
+    for (index, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '=' || c == ':' || is_whitespace(c) {
+            key_end = index;
+            break;
+        }
+    }
+
+    let mut rest = line[key_end..].trim_left_matches(is_whitespace);
+
+    if rest.starts_with('=') || rest.starts_with(':') {
+        rest = rest[1..].trim_left_matches(is_whitespace);
+    }
+
+    (&line[..key_end], rest)
+}
+
+fn unescape(input: &str) -> String {
+
+    let mut output = String::new();
+
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('t') => output.push('\t'),
+            Some('n') => output.push('\n'),
+            Some('r') => output.push('\r'),
+            Some('f') => output.push('\u{c}'),
+            Some('u') => {
+                let high = parse_hex(chars.as_str());
+
+                // Characters outside of the BMP are written as a pair of surrogate escapes
+                let surrogate = high.and_then(|high| match high {
+                    0xD800..=0xDBFF if chars.as_str()[4..].starts_with("\\u") => parse_hex(&chars.as_str()[6..])
+                        .and_then(|low| match low {
+                            0xDC00..=0xDFFF => Some(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)),
+                            _ => None
+                        }),
+                    _ => None
+                });
+
+                match (surrogate.and_then(::std::char::from_u32), high.and_then(::std::char::from_u32)) {
+                    (Some(unicode), _) => {
+                        output.push(unicode);
+                        chars = chars.as_str()[10..].chars();
+                    },
+                    (None, Some(unicode)) => {
+                        output.push(unicode);
+                        chars = chars.as_str()[4..].chars();
+                    },
+                    // Malformed escapes are kept as they were rather than failing the whole file
+                    _ => output.push_str("\\u")
+                }
+            },
+            Some(other) => output.push(other),
+            None => {}
+        }
+    }
+
+    output
+}
+
+fn parse_hex(input: &str) -> Option<u32> {
+    match input.get(..4) {
+        Some(hex) if hex.chars().all(|c| c.is_digit(16)) => u32::from_str_radix(hex, 16).ok(),
+        _ => None
+    }
+}
+
+/*
+  Escapes the same way `Properties.store` does: all spaces in keys, leading spaces in values, separators, comment characters and anything outside of printable ASCII
+*/
+
+fn escape(input: &str, is_key: bool) -> String {
+
+    let mut output = String::new();
+
+    for (index, c) in input.chars().enumerate() {
+        match c {
+            ' ' if is_key || index == 0 => output.push_str("\\ "),
+            '\\' => output.push_str("\\\\"),
+            '\t' => output.push_str("\\t"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\u{c}' => output.push_str("\\f"),
+            '=' | ':' | '#' | '!' => {
+                output.push('\\');
+                output.push(c);
+            },
+            c if c < ' ' || c > '~' => {
+                let mut units = [0u16; 2];
+                for unit in c.encode_utf16(&mut units).iter() {
+                    output.push_str(&format!("\\u{:04X}", unit));
+                }
+            },
+            c => output.push(c)
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::Properties;
+
+    #[test]
+    fn separators() {
+
+        let properties = Properties::parse("equals=1\ncolon:2\nspace 3\n  padded  =  4\ntab\t5\nempty\nescaped\\=key=6\n");
+
+        assert_eq!(properties.get("equals"), Some("1"));
+        assert_eq!(properties.get("colon"), Some("2"));
+        assert_eq!(properties.get("space"), Some("3"));
+        assert_eq!(properties.get("padded"), Some("4"));
+        assert_eq!(properties.get("tab"), Some("5"));
+        assert_eq!(properties.get("empty"), Some(""));
+        assert_eq!(properties.get("escaped=key"), Some("6"));
+    }
+
+    #[test]
+    fn only_first_separator_splits() {
+
+        let properties = Properties::parse("key = = value\nurl=http://example.com:8080\nspaced   : value\n");
+
+        assert_eq!(properties.get("key"), Some("= value"));
+        assert_eq!(properties.get("url"), Some("http://example.com:8080"));
+        assert_eq!(properties.get("spaced"), Some("value"));
+    }
+
+    #[test]
+    fn comments() {
+
+        let properties = Properties::parse("# comment\n! also a comment\n   # indented comment\nkey=value # not a comment\n");
+
+        assert_eq!(properties.iter().count(), 1);
+        assert_eq!(properties.get("key"), Some("value # not a comment"));
+    }
+
+    #[test]
+    fn comments_do_not_continue() {
+
+        let properties = Properties::parse("# comment ending in \\\nkey=value\n");
+
+        assert_eq!(properties.get("key"), Some("value"));
+        assert_eq!(properties.iter().count(), 1);
+    }
+
+    #[test]
+    fn continuations() {
+
+        let properties = Properties::parse("list=one, \\\n    two, \\\n    three\nnext=value\n");
+
+        assert_eq!(properties.get("list"), Some("one, two, three"));
+        assert_eq!(properties.get("next"), Some("value"));
+    }
+
+    #[test]
+    fn continuation_keeps_comment_characters() {
+
+        let properties = Properties::parse("key=value \\\n  # still the value\n");
+
+        assert_eq!(properties.get("key"), Some("value # still the value"));
+    }
+
+    #[test]
+    fn escaped_backslash_does_not_continue() {
+
+        let properties = Properties::parse("path=C:\\\\\nnext=value\n");
+
+        assert_eq!(properties.get("path"), Some("C:\\"));
+        assert_eq!(properties.get("next"), Some("value"));
+    }
+
+    #[test]
+    fn continuation_at_end_of_file() {
+
+        let properties = Properties::parse("key=value\\");
+
+        assert_eq!(properties.get("key"), Some("value"));
+    }
+
+    #[test]
+    fn line_endings() {
+
+        let properties = Properties::parse("windows=1\r\nmac=2\runix=3\n");
+
+        assert_eq!(properties.get("windows"), Some("1"));
+        assert_eq!(properties.get("mac"), Some("2"));
+        assert_eq!(properties.get("unix"), Some("3"));
+    }
+
+    #[test]
+    fn escapes() {
+
+        let properties = Properties::parse("key=tab\\tnewline\\nreturn\\rfeed\\fother\\q\n");
+
+        assert_eq!(properties.get("key"), Some("tab\tnewline\nreturn\rfeed\u{c}otherq"));
+    }
+
+    #[test]
+    fn unicode_escapes() {
+
+        let properties = Properties::parse("key=caf\\u00e9\nupper=\\u00C9\n");
+
+        assert_eq!(properties.get("key"), Some("café"));
+        assert_eq!(properties.get("upper"), Some("É"));
+    }
+
+    #[test]
+    fn surrogate_pairs() {
+
+        let properties = Properties::parse("emoji=\\uD83D\\uDE00!\n");
+
+        assert_eq!(properties.get("emoji"), Some("\u{1F600}!"));
+    }
+
+    #[test]
+    fn malformed_unicode_escapes() {
+
+        let properties = Properties::parse("short=\\u12\nnot_hex=\\uZZZZ\nlone=\\uD83Dx\nunpaired=\\uD83D\\u0041\n");
+
+        assert_eq!(properties.get("short"), Some("\\u12"));
+        assert_eq!(properties.get("not_hex"), Some("\\uZZZZ"));
+        assert_eq!(properties.get("lone"), Some("\\uD83Dx"));
+        assert_eq!(properties.get("unpaired"), Some("\\uD83DA"));
+    }
+
+    #[test]
+    fn utf8() {
+
+        let properties = Properties::read("key=café\n".as_bytes()).unwrap();
+
+        assert_eq!(properties.get("key"), Some("café"));
+    }
+
+    #[test]
+    fn latin1_fallback() {
+
+        let properties = Properties::read(&b"key=caf\xe9\n"[..]).unwrap();
+
+        assert_eq!(properties.get("key"), Some("café"));
+    }
+
+    #[test]
+    fn later_values_replace_earlier_ones() {
+
+        let properties = Properties::parse("a=1\nb=2\na=3\n");
+
+        assert_eq!(properties.get("a"), Some("3"));
+        assert_eq!(properties.iter().map(|&(ref key, _)| key.as_str()).collect::<Vec<_>>(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn write() {
+
+        let mut properties = Properties::new();
+
+        properties.set("module.id", "my-module");
+        properties.set("key with spaces", " leading space");
+        properties.set("separators", "a=b:c#d!e");
+        properties.set("unicode", "café \u{1F600}");
+
+        assert_eq!(properties.to_string(), concat!(
+            "module.id=my-module\n",
+            "key\\ with\\ spaces=\\ leading space\n",
+            "separators=a\\=b\\:c\\#d\\!e\n",
+            "unicode=caf\\u00E9 \\uD83D\\uDE00\n"
+        ));
+    }
+
+    #[test]
+    fn round_trip() {
+
+        let mut properties = Properties::new();
+
+        properties.set("simple", "value");
+        properties.set("key with spaces", "  leading and trailing  ");
+        properties.set("separators=:", "=:#!");
+        properties.set("#comment", "!not a comment");
+        properties.set("control", "tab\tnewline\nreturn\rfeed\u{c}");
+        properties.set("backslash\\", "ends with \\");
+        properties.set("unicode", "café \u{1F600} 日本");
+        properties.set("empty", "");
+
+        let written = properties.to_string();
+
+        assert_eq!(Properties::parse(&written), properties);
+        assert_eq!(Properties::read(written.as_bytes()).unwrap(), properties);
+    }
+
+    #[test]
+    fn round_trip_keeps_order() {
+
+        let properties = Properties::parse("z=1\na=2\nm=3\n");
+
+        assert_eq!(properties.to_string(), "z=1\na=2\nm=3\n");
+    }
+}
@@ -0,0 +1,147 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+
+use regex::Regex;
+
+use error::{ParampError, Result};
+use validate;
+use {read_file, resolve_file};
+
+/*
+  Writes upgraded versions from `--check` back into the yaml file.  Only the version in each `alfresco_modules` entry is changed, the rest of the file (comments, ordering, quoting and other keys) is left exactly as it was
+*/
+
+lazy_static! {
+    static ref MODULE_ENTRY: Regex = Regex::new(r"^(profiles\.[^.\[]+\.)?alfresco_modules(\.replace)?\[\d+\]$").unwrap();
+}
+
+#[derive(Debug, Clone)]
+pub struct Upgrade {
+    pub module: String,
+    pub from: String,
+    pub to: String
+}
+
+struct Edit<'a> {
+    upgrade: &'a Upgrade,
+    start: usize,
+    end: usize,
+    line: usize
+}
+
+pub fn write_upgrades(input_file: &str, upgrades: &[Upgrade], interactive: bool) -> Result<()> {
+
+    let contents = resolve_file(input_file)
+        .and_then(read_file)
+        .map_err(|err| ParampError::io(format!("Could not read '{}'", input_file), err))?;
+
+    let positions = validate::value_positions(input_file, &contents)?;
+
+    //Markers count characters, not bytes
+    let offsets: Vec<usize> = contents.char_indices().map(|(offset, _)| offset).collect();
+
+    let mut entries: Vec<_> = positions.iter()
+        .filter(|&(path, _)| MODULE_ENTRY.is_match(path))
+        .filter_map(|(_, mark)| offsets.get(mark.index()).map(|&offset| (offset, mark.line())))
+        .collect();
+
+    entries.sort();
+
+    let mut edits = Vec::new();
+
+    for &(offset, line) in entries.iter() {
+
+        let start = match contents[offset..].chars().next() {
+            Some('"') | Some('\'') => offset + 1,
+            _ => offset
+        };
+
+        let text = &contents[start..];
+
+        let upgrade = upgrades.iter().find(|upgrade| {
+
+            let current = format!("{}:{}", upgrade.module, upgrade.from);
+
+            //The version has to match exactly, i.e, `1.0` shouldn't match `1.0.1`
+            text.starts_with(&current) && match text[current.len()..].chars().next() {
+                Some(next) => !(next.is_alphanumeric() || next == '.' || next == '-' || next == '_'),
+                None => true
+            }
+        });
+
+        if let Some(upgrade) = upgrade {
+            let version_start = start + upgrade.module.len() + 1;
+
+            edits.push(Edit {
+                upgrade: upgrade,
+                start: version_start,
+                end: version_start + upgrade.from.len(),
+                line: line
+            });
+        }
+    }
+
+    for upgrade in upgrades.iter() {
+        if !edits.iter().any(|edit| edit.upgrade.module == upgrade.module) {
+            println!("Could not find '{}:{}' in '{}', it may come from a file it extends or an environment variable", upgrade.module, upgrade.from, input_file);
+        }
+    }
+
+    let mut accepted = Vec::new();
+
+    for edit in edits.into_iter() {
+        if !interactive || confirm(&format!("Upgrade '{}' from '{}' to '{}' ({}:{})?", edit.upgrade.module, edit.upgrade.from, edit.upgrade.to, input_file, edit.line))? {
+            accepted.push(edit);
+        }
+    }
+
+    if accepted.is_empty() {
+        println!("\nNo modules were updated in '{}'", input_file);
+        return Ok(());
+    }
+
+    let mut output = contents.clone();
+
+    //From the end of the file back, so earlier offsets stay valid
+    for edit in accepted.iter().rev() {
+        output = format!("{}{}{}", &output[..edit.start], edit.upgrade.to, &output[edit.end..]);
+    }
+
+    //Written next to the yaml file and moved over it, so a failed write never leaves it half written
+    let temp_file = format!("{}.tmp", input_file);
+
+    let write_error = |err| ParampError::io(format!("Could not write '{}'", temp_file), err);
+
+    let mut file_handle = File::create(&temp_file).map_err(&write_error)?;
+
+    file_handle.write_all(output.as_bytes()).map_err(&write_error)?;
+
+    file_handle.sync_all().map_err(&write_error)?;
+
+    if let Ok(metadata) = fs::metadata(input_file) {
+        fs::set_permissions(&temp_file, metadata.permissions()).map_err(&write_error)?;
+    }
+
+    fs::rename(&temp_file, input_file).map_err(|err| ParampError::io(format!("Could not move '{}' over '{}'", temp_file, input_file), err))?;
+
+    println!("");
+
+    for edit in accepted.iter() {
+        println!("Updated '{}' from '{}' to '{}' ({}:{})", edit.upgrade.module, edit.upgrade.from, edit.upgrade.to, input_file, edit.line);
+    }
+
+    Ok(())
+}
+
+fn confirm(question: &str) -> Result<bool> {
+
+    print!("{} [y/N] ", question);
+
+    io::stdout().flush().map_err(|err| ParampError::io(String::from("Could not write to stdout"), err))?;
+
+    let mut answer = String::new();
+
+    io::stdin().read_line(&mut answer).map_err(|err| ParampError::io(String::from("Could not read from stdin"), err))?;
+
+    Ok(answer.trim().eq_ignore_ascii_case("y") || answer.trim().eq_ignore_ascii_case("yes"))
+}
@@ -0,0 +1,419 @@
+use std::cmp;
+use std::collections::HashMap;
+
+use yaml_rust::Yaml;
+use yaml_rust::parser::{Parser, Event, MarkedEventReceiver};
+use yaml_rust::scanner::Marker;
+
+use error::{ParampError, Result};
+
+/*
+  The yaml getters return `None` for anything that's missing or the wrong type, so a typo like `alfresco_module:` would otherwise quietly do nothing.  The whole document is checked against the keys paramp knows about before anything is run
+*/
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    String,
+    Bool,
+    StringList,
+    FileList,
+    Paths,
+    Profiles,
+    OneOf(&'static [&'static str])
+}
+
+const CONFLICT_MODES: &'static [&'static str] = &["warn", "error", "last_wins", "first_wins"];
+
+//Keys that can only be used at the top of a file, not in a profile
+const DOCUMENT: &'static [(&'static str, Kind)] = &[
+    ("extends", Kind::Paths),
+    ("profiles", Kind::Profiles)
+];
+
+const TOP_LEVEL: &'static [(&'static str, Kind)] = &[
+    ("files", Kind::FileList),
+    ("output_dir", Kind::String),
+    ("output_war", Kind::String),
+    ("alfresco_modules", Kind::StringList),
+    //Extra archives for a module type, i.e, `amps_repo` or `amps_share`
+    ("amps_*", Kind::FileList),
+    ("url", Kind::String),
+    ("token", Kind::String),
+    ("token_env", Kind::String),
+    ("token_file", Kind::String),
+    ("type", Kind::String),
+    ("development", Kind::Bool),
+    ("on_conflict", Kind::OneOf(CONFLICT_MODES)),
+    ("allow_conflicts", Kind::StringList),
+    ("include", Kind::StringList),
+    ("exclude", Kind::StringList),
+    ("keep_previous", Kind::Bool),
+    ("incremental", Kind::Bool),
+    ("strict_deps", Kind::Bool),
+    ("strict_versions", Kind::Bool),
+    ("reproducible", Kind::Bool),
+    ("locked", Kind::Bool)
+];
+
+const FILE_ENTRY: &'static [(&'static str, Kind)] = &[
+    ("path", Kind::String),
+    ("target", Kind::String),
+    ("include", Kind::StringList),
+    ("exclude", Kind::StringList),
+    ("optional", Kind::Bool),
+    ("sha256", Kind::String)
+];
+
+/*
+  Where each key and value starts in the source, keyed by its path in the document (i.e, `files[1].path`).  `Yaml` doesn't keep track of this, so the source is parsed again just for the markers
+*/
+
+#[derive(Default)]
+struct Positions {
+    stack: Vec<Frame>,
+    keys: HashMap<String, Marker>,
+    values: HashMap<String, Marker>
+}
+
+enum Frame {
+    Mapping(Option<String>),
+    Sequence(usize)
+}
+
+impl Positions {
+
+    fn path(&self) -> String {
+
+        let mut path = String::new();
+
+        for frame in self.stack.iter() {
+            match *frame {
+                Frame::Mapping(Some(ref key)) => {
+                    if !path.is_empty() {
+                        path.push('.');
+                    }
+                    path.push_str(key);
+                },
+                Frame::Mapping(None) => {},
+                Frame::Sequence(index) => path.push_str(&format!("[{}]", index))
+            }
+        }
+
+        path
+    }
+
+    fn is_key(&self) -> bool {
+        match self.stack.last() {
+            Some(&Frame::Mapping(None)) => true,
+            _ => false
+        }
+    }
+
+    //Records a new node as either the next key of a mapping or the next value of its parent
+    fn node(&mut self, key: Option<&str>, mark: Marker) {
+
+        if self.is_key() {
+
+            //Block mappings are marked from the end of their first key, so the mapping is moved back to the start of it
+            let mapping = self.path();
+
+            if let Some(start) = self.values.get_mut(&mapping) {
+                if mark.index() < start.index() {
+                    *start = mark;
+                }
+            }
+
+            if let Some(&mut Frame::Mapping(ref mut current)) = self.stack.last_mut() {
+                *current = Some(String::from(key.unwrap_or("")));
+            }
+
+            let path = self.path();
+            self.keys.insert(path, mark);
+            return;
+        }
+
+        let path = self.path();
+        self.values.entry(path).or_insert(mark);
+    }
+
+    //Moves the parent on once a value (including everything inside it) is finished
+    fn advance(&mut self) {
+        match self.stack.last_mut() {
+            Some(&mut Frame::Mapping(ref mut current)) => *current = None,
+            Some(&mut Frame::Sequence(ref mut index)) => *index += 1,
+            None => {}
+        }
+    }
+}
+
+impl MarkedEventReceiver for Positions {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::Scalar(ref value, _, _, _) => {
+                let is_key = self.is_key();
+                self.node(Some(value), mark);
+                if !is_key {
+                    self.advance();
+                }
+            },
+            Event::Alias(_) => {
+                self.node(None, mark);
+                self.advance();
+            },
+            Event::SequenceStart(_) => {
+                self.node(None, mark);
+                self.stack.push(Frame::Sequence(0));
+            },
+            Event::MappingStart(_) => {
+                self.node(None, mark);
+                self.stack.push(Frame::Mapping(None));
+            },
+            Event::SequenceEnd | Event::MappingEnd => {
+                self.stack.pop();
+                self.advance();
+            },
+            _ => {}
+        }
+    }
+}
+
+struct Validator<'a> {
+    input_file: &'a str,
+    positions: Positions,
+    problems: Vec<String>
+}
+
+impl<'a> Validator<'a> {
+
+    fn report(&mut self, mark: Option<Marker>, message: String) {
+        match mark {
+            Some(mark) => self.problems.push(format!("{}:{}:{}: {}", self.input_file, mark.line(), mark.col() + 1, message)),
+            None => self.problems.push(format!("{}: {}", self.input_file, message))
+        }
+    }
+
+    fn check_mapping(&mut self, yaml: &Yaml, path: &str, schema: &[(&'static str, Kind)]) {
+
+        let hash = match *yaml {
+            Yaml::Hash(ref hash) => hash,
+            _ => return
+        };
+
+        for (key, value) in hash.iter() {
+
+            let name = match *key {
+                Yaml::String(ref name) => name.clone(),
+                _ => {
+                    let mark = self.positions.values.get(path).cloned();
+                    self.report(mark, format!("keys must be strings, got {}", describe(key)));
+                    continue;
+                }
+            };
+
+            let child = match path {
+                "" => name.clone(),
+                _ => format!("{}.{}", path, name)
+            };
+
+            match schema.iter().find(|&&(known, _)| matches_key(known, &name)) {
+                Some(&(_, kind)) => self.check_value(value, &child, kind),
+                None => {
+                    let mark = self.positions.keys.get(&child).cloned();
+
+                    let message = match suggest(&name, schema) {
+                        Some(suggestion) => format!("unknown key '{}', did you mean '{}'?", child, suggestion),
+                        None => format!("unknown key '{}'", child)
+                    };
+
+                    self.report(mark, message);
+                }
+            }
+        }
+    }
+
+    fn check_value(&mut self, value: &Yaml, path: &str, kind: Kind) {
+
+        let mark = self.positions.values.get(path).cloned();
+
+        match (kind, value) {
+            (Kind::String, &Yaml::String(_)) | (Kind::Bool, &Yaml::Boolean(_)) => {},
+            (Kind::OneOf(allowed), &Yaml::String(ref string)) => {
+                //`${VAR}` is only known once it's interpolated, and checked then
+                if !string.contains("${") && !allowed.contains(&string.as_str()) {
+                    self.report(mark, format!("'{}' must be one of {}, got '{}'", path, allowed.join(", "), string));
+                }
+            },
+            (Kind::StringList, &Yaml::Array(ref array)) => {
+                for (index, item) in array.iter().enumerate() {
+                    if item.as_str().is_none() {
+                        let item_path = format!("{}[{}]", path, index);
+                        let item_mark = self.positions.values.get(&item_path).cloned();
+                        self.report(item_mark, format!("'{}' must be a string, got {}", item_path, describe(item)));
+                    }
+                }
+            },
+            (Kind::FileList, &Yaml::Array(ref array)) => {
+                for (index, item) in array.iter().enumerate() {
+
+                    let item_path = format!("{}[{}]", path, index);
+
+                    match *item {
+                        Yaml::String(_) => {},
+                        Yaml::Hash(_) => {
+                            if item["path"].is_badvalue() {
+                                let item_mark = self.positions.values.get(&item_path).cloned();
+                                self.report(item_mark, format!("'{}' must have a path", item_path));
+                            }
+                            self.check_mapping(item, &item_path, FILE_ENTRY);
+                        },
+                        _ => {
+                            let item_mark = self.positions.values.get(&item_path).cloned();
+                            self.report(item_mark, format!("'{}' must be a path or a mapping with a path, got {}", item_path, describe(item)));
+                        }
+                    }
+                }
+            },
+            (Kind::Paths, &Yaml::String(_)) => {},
+            (Kind::Profiles, &Yaml::Hash(ref profiles)) => {
+                for (name, profile) in profiles.iter() {
+
+                    let profile_path = format!("{}.{}", path, name.as_str().unwrap_or(""));
+
+                    match *profile {
+                        Yaml::Hash(_) => self.check_mapping(profile, &profile_path, TOP_LEVEL),
+                        Yaml::Null => {},
+                        _ => {
+                            let profile_mark = self.positions.values.get(&profile_path).cloned();
+                            self.report(profile_mark, format!("'{}' must be a mapping, got {}", profile_path, describe(profile)));
+                        }
+                    }
+                }
+            },
+            (Kind::Paths, &Yaml::Array(_)) => self.check_value(value, path, Kind::StringList),
+            //Lists can replace the list from the file they extend (see `compose::merge`)
+            (Kind::StringList, &Yaml::Hash(ref hash)) | (Kind::FileList, &Yaml::Hash(ref hash)) if hash.len() == 1 && !value["replace"].is_badvalue() => {
+                self.check_value(&value["replace"], &format!("{}.replace", path), kind);
+            },
+            //An empty value (`files:` with nothing after it) is the same as leaving the key out
+            (_, &Yaml::Null) => {},
+            (kind, value) => {
+                self.report(mark, format!("'{}' must be {}, got {}", path, expected(kind), describe(value)));
+            }
+        }
+    }
+}
+
+fn parse_positions(input_file: &str, contents: &str) -> Result<Positions> {
+
+    let mut positions = Positions::default();
+
+    let mut parser = Parser::new(contents.chars());
+
+    parser.load(&mut positions, false).map_err(|err| ParampError::Config(format!("Could not parse '{}': {}", input_file, err)))?;
+
+    Ok(positions)
+}
+
+//Where each value starts, so the file can be edited in place without losing comments (see `rewrite`)
+
+pub fn value_positions(input_file: &str, contents: &str) -> Result<HashMap<String, Marker>> {
+    parse_positions(input_file, contents).map(|positions| positions.values)
+}
+
+/*
+  Checks a parsed yaml document against the schema, returning every problem found rather than just the first
+*/
+
+pub fn validate(input_file: &str, contents: &str, yaml: &Yaml) -> Result<()> {
+
+    let positions = parse_positions(input_file, contents)?;
+
+    let mut validator = Validator {
+        input_file: input_file,
+        positions: positions,
+        problems: Vec::new()
+    };
+
+    match *yaml {
+        Yaml::Hash(_) => {
+            let schema: Vec<(&'static str, Kind)> = DOCUMENT.iter().chain(TOP_LEVEL.iter()).cloned().collect();
+            validator.check_mapping(yaml, "", &schema);
+        },
+        Yaml::Null => {},
+        ref other => {
+            let message = format!("the document must be a mapping, got {}", describe(other));
+            validator.report(None, message);
+        }
+    }
+
+    match validator.problems.len() {
+        0 => Ok(()),
+        count => Err(ParampError::Config(format!("'{}' has {} problems:\n  {}", input_file, count, validator.problems.join("\n  "))))
+    }
+}
+
+fn describe(value: &Yaml) -> String {
+    match *value {
+        Yaml::String(ref string) => format!("the string '{}'", string),
+        Yaml::Integer(number) => format!("the number {}", number),
+        Yaml::Real(ref number) => format!("the number {}", number),
+        Yaml::Boolean(boolean) => format!("{}", boolean),
+        Yaml::Array(_) => String::from("a list"),
+        Yaml::Hash(_) => String::from("a mapping"),
+        Yaml::Null => String::from("nothing"),
+        _ => String::from("an unsupported value")
+    }
+}
+
+fn expected(kind: Kind) -> &'static str {
+    match kind {
+        Kind::String | Kind::OneOf(_) => "a string",
+        Kind::Bool => "true or false",
+        Kind::StringList => "a list of strings",
+        Kind::FileList => "a list of files",
+        Kind::Paths => "a path or a list of paths",
+        Kind::Profiles => "a mapping of profile names to settings"
+    }
+}
+
+//Keys ending in `*` match anything starting with the rest of the key
+
+fn matches_key(known: &str, name: &str) -> bool {
+    match known.ends_with('*') {
+        true => name.len() >= known.len() && name.starts_with(&known[..known.len() - 1]),
+        false => known == name
+    }
+}
+
+//The closest known key, if it's close enough to be a likely typo
+
+fn suggest(name: &str, schema: &[(&'static str, Kind)]) -> Option<&'static str> {
+    schema.iter()
+        .filter(|&&(known, _)| !known.ends_with('*'))
+        .map(|&(known, _)| (known, edit_distance(name, known)))
+        .filter(|&(known, distance)| distance <= 2 || distance * 3 <= known.len())
+        .min_by_key(|&(_, distance)| distance)
+        .map(|(known, _)| known)
+}
+
+fn edit_distance(left: &str, right: &str) -> usize {
+
+    let right: Vec<char> = right.chars().collect();
+
+    let mut previous: Vec<usize> = (0..right.len() + 1).collect();
+
+    for (i, left_char) in left.chars().enumerate() {
+
+        let mut current = vec![i + 1];
+
+        for (j, right_char) in right.iter().enumerate() {
+            let cost = if left_char == *right_char { 0 } else { 1 };
+            let value = cmp::min(cmp::min(previous[j + 1] + 1, current[j] + 1), previous[j] + cost);
+            current.push(value);
+        }
+
+        previous = current;
+    }
+
+    previous[right.len()]
+}
@@ -0,0 +1,234 @@
+use zip::read::ZipArchive;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crc::crc32::{self, Hasher32};
+
+use serde_json;
+
+use extract::{Plan, Source};
+use install::{sha256_file, INSTALL_MANIFEST_PATH};
+
+use crypto::sha2::Sha256;
+use crypto::digest::Digest;
+use error::{ParampError, Result};
+use resolve_file;
+
+/*
+  The difference between what a plan would write and what's actually in an output directory
+*/
+
+#[derive(Debug, Default, Serialize)]
+pub struct Drift {
+    pub added: Vec<String>,
+    pub missing: Vec<String>,
+    pub modified: Vec<String>
+}
+
+impl Drift {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.missing.is_empty() && self.modified.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checksum {
+    pub size: u64,
+    pub crc: u32
+}
+
+/*
+  Works out the size and CRC32 of every planned file.  For archive entries this comes straight from the zip's central directory, so nothing needs to be decompressed
+*/
+
+pub fn expected_checksums(plan: &Plan) -> Result<BTreeMap<String, Checksum>> {
+
+    let mut checksums = BTreeMap::new();
+
+    for input_file in plan.archives.iter() {
+
+        let file = resolve_file(input_file).map_err(|err| ParampError::archive(input_file, err))?;
+
+        let mut archive = ZipArchive::new(file).map_err(|err| ParampError::archive(input_file, err))?;
+
+        let mut entries: HashMap<String, Checksum> = HashMap::new();
+
+        for i in 0..archive.len() {
+            let file = archive.by_index(i).map_err(|err| ParampError::archive(input_file, err))?;
+            entries.insert(String::from(file.name()), Checksum {
+                size: file.size(),
+                crc: file.crc32()
+            });
+        }
+
+        for (path, planned) in plan.files.iter().filter(|&(_, planned)| planned.archive == *input_file) {
+            let checksum = match planned.source {
+                Source::Entry(ref name) => match entries.get(name) {
+                    Some(checksum) => *checksum,
+                    None => return Err(ParampError::Archive(format!("Could not find '{}' in '{}'", name, input_file)))
+                },
+                Source::Content(ref content) => Checksum {
+                    size: content.len() as u64,
+                    crc: crc32::checksum_ieee(content)
+                }
+            };
+
+            checksums.insert(path.clone(), checksum);
+        }
+    }
+
+    Ok(checksums)
+}
+
+pub fn file_checksum(path: &Path) -> Option<Checksum> {
+
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return None
+    };
+
+    let mut digest = crc32::Digest::new(crc32::IEEE);
+
+    let mut size: u64 = 0;
+
+    let mut buffer = [0; 64 * 1024];
+
+    loop {
+        match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => {
+                digest.write(&buffer[..read]);
+                size += read as u64;
+            },
+            Err(_) => return None
+        }
+    }
+
+    Some(Checksum {
+        size: size,
+        crc: digest.sum32()
+    })
+}
+
+/*
+  Lists every file under a directory, relative to that directory with `/` separators
+*/
+
+pub fn list_files(dir: &Path) -> BTreeSet<String> {
+
+    let mut files = BTreeSet::new();
+
+    add_files(dir, "", &mut files);
+
+    files
+}
+
+fn add_files(dir: &Path, prefix: &str, files: &mut BTreeSet<String>) {
+
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries {
+            if let Ok(entry) = entry {
+
+                let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+
+                match entry.file_type() {
+                    Ok(ref file_type) if file_type.is_dir() => add_files(&entry.path(), &format!("{}/", name), files),
+                    Ok(_) => {
+                        files.insert(name);
+                    },
+                    Err(_) => {}
+                }
+            }
+        }
+    }
+}
+
+/*
+  A single digest for a whole directory, covering the path and content of every file.  Directory and file times aren't included
+*/
+
+pub fn tree_digest(dir: &str) -> Result<String> {
+
+    let mut sh = Sha256::new();
+
+    for path in list_files(Path::new(dir)).iter() {
+
+        let output_file = format!("{}/{}", dir, path);
+
+        let checksum = sha256_file(&output_file).map_err(|err| ParampError::io(format!("Could not read '{}' for digest", output_file), err))?;
+
+        sh.input_str(&format!("{}\0{}\n", path, checksum));
+    }
+
+    Ok(sh.result_str())
+}
+
+pub fn compare_output(plan: &Plan, output_dir: &str) -> Result<Drift> {
+
+    let mut drift = Drift::default();
+
+    let output = Path::new(output_dir);
+
+    let expected = expected_checksums(plan)?;
+
+    let existing = list_files(output);
+
+    for (path, checksum) in expected.iter() {
+        if !existing.contains(path) {
+            drift.missing.push(path.clone());
+        } else {
+
+            let file_path = output.join(path);
+
+            //Only bother reading the file if the size matches
+            let matches = match fs::metadata(&file_path) {
+                Ok(ref metadata) if metadata.len() == checksum.size => file_checksum(&file_path) == Some(*checksum),
+                _ => false
+            };
+
+            if !matches {
+                drift.modified.push(path.clone());
+            }
+        }
+    }
+
+    //The install manifest records when it was written, so will never match
+    for path in existing.iter().filter(|path| *path != INSTALL_MANIFEST_PATH) {
+        if !expected.contains_key(path) {
+            drift.added.push(path.clone());
+        }
+    }
+
+    Ok(drift)
+}
+
+pub fn print_drift(drift: &Drift, output_dir: &str, json: bool) {
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(drift).unwrap_or_default());
+        return;
+    }
+
+    if drift.is_empty() {
+        println!("{} matches the yaml file", output_dir);
+        return;
+    }
+
+    println!("{} has drifted from the yaml file:\n", output_dir);
+
+    for path in drift.added.iter() {
+        println!("  added:    /{}", path);
+    }
+
+    for path in drift.missing.iter() {
+        println!("  missing:  /{}", path);
+    }
+
+    for path in drift.modified.iter() {
+        println!("  modified: /{}", path);
+    }
+
+    println!("\n{} added, {} missing, {} modified", drift.added.len(), drift.missing.len(), drift.modified.len());
+}
@@ -0,0 +1,110 @@
+use zip::read::ZipArchive;
+use zip::write::{ZipWriter, FileOptions};
+use std::fs::{self, File};
+use std::io::{copy, Write};
+use std::collections::HashMap;
+
+use serde_json;
+use time::{self, Timespec};
+use std::cmp;
+
+use extract::{Plan, PlannedFile, Source, MANIFEST_PATH};
+use install::{InstallManifest, INSTALL_MANIFEST_PATH};
+use error::{ParampError, Result};
+use {create_file_and_dirs, resolve_file};
+
+const DOS_EPOCH: i64 = 315532800;
+
+/*
+  Streams a plan straight into a war file, rather than an exploded directory.
+
+  The JAR spec requires the manifest to be the first entry (after its directory), so it's written before anything else.  The war is written to a temporary file first and renamed into place once complete
+*/
+
+pub fn write_war(plan: &Plan, war_file: &str, install_manifest: &InstallManifest) -> Result<()> {
+
+    println!("Creating war: {}", war_file);
+
+    let temp_file = format!("{}.tmp", war_file);
+
+    let file_handle = create_file_and_dirs(&temp_file).map_err(|err| ParampError::io(format!("Could not create war file '{}'", temp_file), err))?;
+
+    let mut zip = ZipWriter::new(file_handle);
+
+    //Directories and the install manifest use the build time, so they're the same for reproducible builds
+    let options = FileOptions::default().last_modified_time(zip_time(plan.build_time()));
+
+    let mut archives: HashMap<&str, ZipArchive<File>> = HashMap::new();
+
+    for input_file in plan.archives.iter() {
+        let file = resolve_file(input_file).map_err(|err| ParampError::archive(input_file, err))?;
+        archives.insert(input_file, ZipArchive::new(file).map_err(|err| ParampError::archive(input_file, err))?);
+    }
+
+    //Any failure writing the zip is an IO failure on the war file
+    let write_error = |err| ParampError::io(format!("Could not write war file '{}'", temp_file), err);
+
+    zip.add_directory("META-INF/", options).map_err(&write_error)?;
+
+    if let Some(manifest) = plan.files.get(MANIFEST_PATH) {
+        zip.start_file(MANIFEST_PATH, file_options(plan, manifest)).map_err(&write_error)?;
+        write_source(&mut zip, &manifest.source, &manifest.archive, &mut archives, war_file)?;
+    }
+
+    for dir in plan.dirs.iter().filter(|dir| *dir != "META-INF") {
+        zip.add_directory(format!("{}/", dir), options).map_err(&write_error)?;
+    }
+
+    for (path, planned) in plan.files.iter().filter(|&(path, _)| path != MANIFEST_PATH) {
+        zip.start_file(path.as_str(), file_options(plan, planned)).map_err(&write_error)?;
+        write_source(&mut zip, &planned.source, &planned.archive, &mut archives, war_file)?;
+    }
+
+    zip.start_file(INSTALL_MANIFEST_PATH, options).map_err(&write_error)?;
+    serde_json::to_writer_pretty(&mut zip, install_manifest).map_err(|err| ParampError::io(format!("Could not write install manifest to '{}'", temp_file), err))?;
+
+    zip.finish().map_err(&write_error)?;
+
+    fs::rename(&temp_file, war_file).map_err(|err| ParampError::io(format!("Could not move war file '{}' into place", war_file), err))
+}
+
+fn file_options(plan: &Plan, planned: &PlannedFile) -> FileOptions {
+
+    let options = FileOptions::default().last_modified_time(zip_time(plan.modified_time(planned)));
+
+    match planned.mode {
+        //Without setuid, setgid or sticky bits, as in the output directory
+        Some(mode) => options.unix_permissions(mode & 0o777),
+        None => options
+    }
+}
+
+//Zip times can't go back further than 1980
+
+fn zip_time(modified: i64) -> time::Tm {
+    time::at_utc(Timespec::new(cmp::max(modified, DOS_EPOCH), 0))
+}
+
+fn write_source<W: Write>(writer: &mut W, source: &Source, input_file: &str, archives: &mut HashMap<&str, ZipArchive<File>>, war_file: &str) -> Result<()> {
+
+    let write_error = |err| ParampError::io(format!("Could not write war file '{}'", war_file), err);
+
+    match *source {
+        Source::Entry(ref name) => {
+
+            let archive = match archives.get_mut(input_file) {
+                Some(archive) => archive,
+                None => return Err(ParampError::Archive(format!("Archive '{}' was not opened", input_file)))
+            };
+
+            let mut file = archive.by_name(name).map_err(|err| ParampError::archive(input_file, err))?;
+
+            copy(&mut file, writer).map_err(write_error)?;
+        },
+        Source::Content(ref content) => {
+            writer.write_all(content).map_err(write_error)?;
+        }
+    }
+
+    Ok(())
+}